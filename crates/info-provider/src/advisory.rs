@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    fs::{self, read_dir, read_to_string},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use flate2::read::GzDecoder;
+use rust_version::{RustVersion, VersionReq};
+use serde::Deserialize;
+use tar::Archive;
+
+use crate::{api::Root1, progress::ProgressTask, InfoProvider};
+
+const ARCHIVE: &str = "https://github.com/rustsec/advisory-db/archive/refs/heads/main.tar.gz";
+const FETCHED_FILE: &str = ".fetched";
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Deserialize)]
+struct AdvisoryFile {
+    advisory: AdvisoryMeta,
+    #[serde(default)]
+    versions: Versions,
+}
+
+#[derive(Debug, Deserialize)]
+struct AdvisoryMeta {
+    id: String,
+    package: String,
+    date: Option<String>,
+    url: Option<String>,
+    cvss: Option<String>,
+    informational: Option<String>,
+    withdrawn: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Versions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+
+/// A RustSec advisory, read from the `crates/<name>/<id>.md` files of the advisory-db
+#[derive(Debug, Clone)]
+pub struct Advisory {
+    pub id: String,
+    pub package: String,
+    pub title: String,
+    pub date: Option<String>,
+    pub url: Option<String>,
+    /// CVSS vector
+    pub cvss: Option<String>,
+    /// `unmaintained`, `unsound` or `notice` for advisories that aren't vulnerabilities
+    pub informational: Option<String>,
+    pub patched: Vec<String>,
+    pub unaffected: Vec<String>,
+}
+
+impl Advisory {
+    fn parse(content: &str) -> Option<Self> {
+        let rest = content.trim_start().strip_prefix("```toml")?;
+        let (front, body) = rest.split_once("```")?;
+        let file = toml::from_str::<AdvisoryFile>(front).ok()?;
+        if file.advisory.withdrawn.is_some() {
+            return None;
+        }
+        let title = body
+            .lines()
+            .find_map(|v| v.strip_prefix("# "))
+            .unwrap_or_default()
+            .trim()
+            .to_owned();
+        Some(Self {
+            id: file.advisory.id,
+            package: file.advisory.package,
+            title,
+            date: file.advisory.date,
+            url: file.advisory.url,
+            cvss: file.advisory.cvss,
+            informational: file.advisory.informational,
+            patched: file.versions.patched,
+            unaffected: file.versions.unaffected,
+        })
+    }
+
+    /// Neither patched nor unaffected
+    pub fn affects(&self, version: &RustVersion) -> bool {
+        !self
+            .patched
+            .iter()
+            .chain(&self.unaffected)
+            .filter_map(|v| VersionReq::try_from(v.as_str()).ok())
+            .any(|v| v.matches(version))
+    }
+
+    /// The first release after `version` the advisory doesn't affect
+    pub fn nearest_fix(&self, version: &RustVersion, releases: &[Root1]) -> Option<String> {
+        releases
+            .iter()
+            .filter(|v| !v.yanked)
+            .filter_map(|v| Some((v.ver()?, v)))
+            .filter(|(v, _)| v > version && !v.is_pre_release() && !self.affects(v))
+            .min_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, v)| v.vers.clone())
+    }
+
+    /// CVSS 3 base score with its rating, like `high 7.5`
+    pub fn severity(&self) -> Option<String> {
+        let score = cvss3_score(self.cvss.as_deref()?)?;
+        let rating = match score {
+            0.0 => "none",
+            v if v < 4.0 => "low",
+            v if v < 7.0 => "medium",
+            v if v < 9.0 => "high",
+            _ => "critical",
+        };
+        Some(format!("{rating} {score:.1}"))
+    }
+}
+
+/// Base score of a `CVSS:3.x/...` vector, see the CVSS 3.1 specification
+fn cvss3_score(vector: &str) -> Option<f64> {
+    let mut parts = vector.split('/');
+    if !parts.next()?.starts_with("CVSS:3") {
+        return None;
+    }
+    let metrics = parts
+        .filter_map(|v| v.split_once(':'))
+        .collect::<HashMap<_, _>>();
+    let changed = match *metrics.get("S")? {
+        "U" => false,
+        "C" => true,
+        _ => return None,
+    };
+    let av = match *metrics.get("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        "P" => 0.2,
+        _ => return None,
+    };
+    let ac = match *metrics.get("AC")? {
+        "L" => 0.77,
+        "H" => 0.44,
+        _ => return None,
+    };
+    let pr = match (*metrics.get("PR")?, changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None,
+    };
+    let ui = match *metrics.get("UI")? {
+        "N" => 0.85,
+        "R" => 0.62,
+        _ => return None,
+    };
+    let impact = |key| match metrics.get(key).copied() {
+        Some("H") => Some(0.56),
+        Some("L") => Some(0.22),
+        Some("N") => Some(0.0),
+        _ => None,
+    };
+    let iss: f64 = 1.0 - (1.0 - impact("C")?) * (1.0 - impact("I")?) * (1.0 - impact("A")?);
+    let impact = match changed {
+        false => 6.42 * iss,
+        true => 7.52 * (iss - 0.029) - 3.25 * (iss - 0.02).powi(15),
+    };
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+    let exploitability = 8.22 * av * ac * pr * ui;
+    let score: f64 = match changed {
+        false => impact + exploitability,
+        true => 1.08 * (impact + exploitability),
+    };
+    Some(round_up(score.min(10.0)))
+}
+
+/// Rounds up to one decimal, avoiding floating point artifacts like the spec does
+fn round_up(value: f64) -> f64 {
+    let int = (value * 100_000.0).round() as u64;
+    match int % 10_000 {
+        0 => int as f64 / 100_000.0,
+        _ => ((int / 10_000) + 1) as f64 / 10.0,
+    }
+}
+
+/// Advisories by crate name
+#[derive(Debug, Default)]
+pub struct AdvisoryDb {
+    by_crate: HashMap<String, Vec<Advisory>>,
+}
+
+impl AdvisoryDb {
+    /// Reads a clone of the advisory-db, only `crates/` is used
+    pub fn load(dir: &Path) -> Self {
+        let mut by_crate: HashMap<String, Vec<Advisory>> = HashMap::new();
+        let Ok(crates) = read_dir(dir.join("crates")) else {
+            return Self::default();
+        };
+        for file in crates
+            .filter_map(|v| v.ok())
+            .filter_map(|v| read_dir(v.path()).ok())
+            .flatten()
+            .filter_map(|v| v.ok())
+            .map(|v| v.path())
+            .filter(|v| v.extension().is_some_and(|v| v == "md"))
+        {
+            if let Some(advisory) = read_to_string(&file).ok().and_then(|v| Advisory::parse(&v)) {
+                by_crate
+                    .entry(advisory.package.clone())
+                    .or_default()
+                    .push(advisory);
+            }
+        }
+        Self { by_crate }
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_crate.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_crate.is_empty()
+    }
+
+    /// Advisories of a crate that affect the version
+    pub fn affecting(&self, name: &str, version: &RustVersion) -> Vec<Advisory> {
+        self.by_crate
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|v| v.affects(version))
+            .cloned()
+            .collect()
+    }
+}
+
+fn is_stale(dir: &Path) -> bool {
+    fs::metadata(dir.join(FETCHED_FILE))
+        .and_then(|v| v.modified())
+        .ok()
+        .and_then(|v| SystemTime::now().duration_since(v).ok())
+        .is_none_or(|age| age > MAX_AGE)
+}
+
+/// Downloads the advisories of the advisory-db into `dir`, replacing the previous copy
+async fn download(
+    dir: PathBuf,
+    client: &reqwest::Client,
+    task: &ProgressTask,
+) -> Result<(), anyhow::Error> {
+    task.report("Downloading", None);
+    let bytes = client
+        .get(ARCHIVE)
+        .header("User-Agent", "cargotom")
+        .timeout(Duration::from_secs(5 * 60))
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    task.report("Extracting", None);
+    tokio::task::spawn_blocking(move || extract(&bytes, &dir)).await?
+}
+
+/// Unpacks the `crates/` advisories of the archive next to `dir`, then swaps it in
+fn extract(bytes: &[u8], dir: &Path) -> Result<(), anyhow::Error> {
+    let tmp = dir.with_extension("tmp");
+    let _ = fs::remove_dir_all(&tmp);
+    let mut archive = Archive::new(GzDecoder::new(bytes));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        // `advisory-db-main/crates/<name>/<id>.md`
+        let path = entry.path()?.components().skip(1).collect::<PathBuf>();
+        if path.starts_with("crates") && path.extension().is_some_and(|v| v == "md") {
+            if let Some(parent) = tmp.join(&path).parent() {
+                fs::create_dir_all(parent)?;
+            }
+            entry.unpack(tmp.join(&path))?;
+        }
+    }
+    fs::write(tmp.join(FETCHED_FILE), "")?;
+    let _ = fs::remove_dir_all(dir);
+    fs::rename(&tmp, dir)?;
+    Ok(())
+}
+
+impl InfoProvider {
+    /// Loads the advisory-db from `path`, or from a copy in the storage folder that is
+    /// downloaded when it is missing or older than a day. Returns the number of advisories
+    pub async fn load_advisories(&self, path: Option<PathBuf>) -> usize {
+        let dir = match path {
+            Some(path) => path,
+            None => {
+                let dir = self.root.join("advisory-db");
+                if is_stale(&dir) {
+                    let task = self.progress.begin("Advisory database", false);
+                    match download(dir.clone(), &self.client, &task).await {
+                        Ok(()) => task.finish("Updated"),
+                        Err(e) => task.finish(format!("Failed to download: {e}")),
+                    }
+                }
+                dir
+            }
+        };
+        let db = tokio::task::spawn_blocking(move || AdvisoryDb::load(&dir))
+            .await
+            .unwrap_or_default();
+        let len = db.len();
+        *self.advisories.write().await = db;
+        len
+    }
+
+    /// Advisories that affect a release, empty until [`Self::load_advisories`] ran
+    pub async fn advisories(&self, name: &str, version: &RustVersion) -> Vec<Advisory> {
+        self.advisories.read().await.affecting(name, version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_version::RustVersion;
+
+    use super::{cvss3_score, Advisory};
+
+    const ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2020-0071"
+package = "time"
+date = "2020-11-18"
+url = "https://github.com/time-rs/time/issues/293"
+cvss = "CVSS:3.1/AV:L/AC:H/PR:N/UI:N/S:U/C:N/I:N/A:H"
+
+[versions]
+patched = [">= 0.2.23"]
+unaffected = ["= 0.2.0", "= 0.2.1", "= 0.2.2", "= 0.2.3", "= 0.2.4", "= 0.2.5", "= 0.2.6"]
+```
+
+# Potential segfault in the time crate
+
+Details
+"#;
+
+    #[test]
+    fn parse() {
+        let advisory = Advisory::parse(ADVISORY).unwrap();
+        assert_eq!(advisory.id, "RUSTSEC-2020-0071");
+        assert_eq!(advisory.title, "Potential segfault in the time crate");
+        let ver = |v| RustVersion::try_from(v).unwrap();
+        assert!(advisory.affects(&ver("0.1.43")));
+        assert!(advisory.affects(&ver("0.2.22")));
+        assert!(!advisory.affects(&ver("0.2.3")));
+        assert!(!advisory.affects(&ver("0.3.0")));
+        assert_eq!(advisory.severity().as_deref(), Some("medium 5.1"));
+    }
+
+    #[test]
+    fn scores() {
+        let score = |v| cvss3_score(v).unwrap();
+        assert_eq!(score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"), 9.8);
+        assert_eq!(score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H"), 10.0);
+        assert_eq!(score("CVSS:3.0/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:N"), 0.0);
+        assert!(cvss3_score("CVSS:4.0/AV:N").is_none());
+    }
+}
@@ -4,11 +4,11 @@ use std::{
 };
 
 use reqwest::{header::USER_AGENT, Client};
-use rust_version::RustVersion;
+use rust_version::{RustVersion, VersionReq};
 use serde::{Deserialize, Serialize};
 use tokio::sync::Notify;
 
-use crate::InfoProvider;
+use crate::{progress::percentage, InfoProvider};
 
 pub enum CacheItem<T> {
     Pending(Arc<Notify>),
@@ -78,6 +78,65 @@ impl InfoProvider {
         }
     }
 
+    fn registry_index(&self, registry: Option<&str>) -> String {
+        registry
+            .and_then(|name| self.registry_url(name))
+            .unwrap_or_else(|| self.registry.to_owned())
+    }
+
+    /// Index url of a registry configured in the cargo config
+    pub fn registry_url(&self, name: &str) -> Option<String> {
+        self.registries.read().unwrap().get(name).cloned()
+    }
+
+    /// Name of the configured registry behind a lock file source like `sparse+https://...`
+    pub fn registry_name(&self, source: &str) -> Option<String> {
+        let index = source
+            .strip_prefix("sparse+")
+            .or_else(|| source.strip_prefix("registry+"))?
+            .trim_end_matches('/');
+        self.registries
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(_, v)| v.trim_end_matches('/') == index)
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Fetches the index data of many crates at once, e.g. when a workspace is opened
+    pub async fn prefetch(self: Arc<Self>, mut crates: Vec<(Option<String>, String)>) {
+        crates.sort();
+        crates.dedup();
+        if crates.is_empty() {
+            return;
+        }
+        let task = self.progress.begin("Fetching crate metadata", true);
+        let total = crates.len() as u64;
+        let mut done = 0;
+        for chunk in crates.chunks(8) {
+            if task.is_cancelled() {
+                task.finish(format!("Cancelled after {done}/{total} crates"));
+                return;
+            }
+            let handles = chunk
+                .iter()
+                .cloned()
+                .map(|(registry, name)| {
+                    let info = self.clone();
+                    tokio::spawn(async move {
+                        let _ = info.get_info(registry.as_deref(), &name).await;
+                    })
+                })
+                .collect::<Vec<_>>();
+            for handle in handles {
+                let _ = handle.await;
+                done += 1;
+            }
+            task.report(format!("{done}/{total} crates"), percentage(done, total));
+        }
+        task.finish(format!("Fetched {total} crates"));
+    }
+
     pub async fn get_crate_repository_api(&self, crate_name: &str) -> Option<String> {
         let url = format!("https://crates.io/api/v1/crates/{}", crate_name);
 
@@ -97,9 +156,8 @@ impl InfoProvider {
         registry: Option<&str>,
         name: &str,
     ) -> CacheItemOut<Root1> {
-        let reg = registry
-            .and_then(|name| self.registries.get(name).map(|index| index.as_str()))
-            .unwrap_or(self.registry);
+        let reg = self.registry_index(registry);
+        let reg = reg.as_str();
         let lock = self.info_cache.read().await;
         let cache = match lock.get(reg) {
             Some(v) => v,
@@ -172,9 +230,8 @@ impl InfoProvider {
         registry: Option<&str>,
         name: &str,
     ) -> Result<Vec<Root1>, String> {
-        let reg = registry
-            .and_then(|name| self.registries.get(name).map(|index| index.as_str()))
-            .unwrap_or(self.registry);
+        let reg = self.registry_index(registry);
+        let reg = reg.as_str();
         let fetch = {
             let lock = self.info_cache.read().await;
             match lock.get(reg) {
@@ -310,24 +367,40 @@ async fn info(
         .lines()
         .map(serde_json::from_str)
         .collect::<Result<Vec<Root1>, _>>()?;
-    Ok(data.into_iter().filter(|v| !v.yanked).collect())
+    Ok(data)
 }
 
 #[derive(Deserialize, Clone)]
 pub struct Deps1 {
+    /// Dependency key, differs from the crate name if renamed
     pub name: String,
     pub req: String,
     pub optional: bool,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default = "default_true")]
+    pub default_features: bool,
+    /// `normal`, `dev` or `build`
+    pub kind: Option<String>,
+    /// Crate name of a renamed dependency
+    pub package: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Deserialize, Clone)]
 pub struct Root1 {
     pub name: String,
     pub vers: String,
-    yanked: bool,
+    /// Yanked releases stay in the list so locked and required ones can be reported
+    pub yanked: bool,
     pub deps: Vec<Deps1>,
     pub features: HashMap<String, Vec<String>>,
     pub features2: Option<HashMap<String, Vec<String>>>,
+    /// `rust-version` of the release, missing for releases published before it was recorded
+    pub rust_version: Option<String>,
 }
 
 #[derive(Eq, PartialEq, Serialize, Deserialize, Clone, Copy)]
@@ -357,7 +430,35 @@ fn name_processor(s: &str) -> String {
     }
 }
 
+impl Deps1 {
+    /// A required normal dependency with default features
+    pub fn new(name: &str, req: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            req: req.to_owned(),
+            optional: false,
+            features: vec![],
+            default_features: true,
+            kind: Some("normal".to_owned()),
+            package: None,
+        }
+    }
+}
+
 impl Root1 {
+    /// A release without dependencies and features, like manifests that aren't published
+    pub fn new(name: &str, vers: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            vers: vers.to_owned(),
+            yanked: false,
+            deps: vec![],
+            features: HashMap::new(),
+            features2: None,
+            rust_version: None,
+        }
+    }
+
     pub fn feature_all(&self) -> Vec<String> {
         let f = self.features.keys().cloned();
         let mut opt: Vec<_> = self
@@ -435,4 +536,16 @@ impl Root1 {
     pub fn ver(&self) -> Option<RustVersion> {
         RustVersion::try_from(self.vers.as_str()).ok()
     }
+
+    /// Whether the release builds with `msrv`. Releases without a `rust-version` and
+    /// packages without an msrv are always compatible
+    pub fn supports_rust(&self, msrv: Option<&RustVersion>) -> bool {
+        let (Some(msrv), Some(required)) = (msrv, self.rust_version.as_deref()) else {
+            return true;
+        };
+        // `1.70` means 1.70.0, the requirement fills in the missing parts
+        VersionReq::try_from(format!(">={required}").as_str())
+            .map(|v| v.matches(msrv))
+            .unwrap_or(true)
+    }
 }
@@ -0,0 +1,172 @@
+use std::{
+    fs::{read_dir, read_to_string, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use rust_version::RustVersion;
+use tar::Archive;
+
+use crate::source::cargo_home;
+
+const NAMES: [&str; 6] = [
+    "CHANGELOG.md",
+    "CHANGES.md",
+    "RELEASES.md",
+    "changelog.md",
+    "Changelog.md",
+    "HISTORY.md",
+];
+
+/// Changelog of the newest locally available release of a crate, from the unpacked source in
+/// `$CARGO_HOME/registry/src` or the downloaded `.crate` archive. Returns the release it came
+/// from with the content
+pub fn local_changelog(name: &str) -> Option<(RustVersion, String)> {
+    let registry = cargo_home()?.join("registry");
+    let mut candidates = releases(&registry.join("src"), name, "")
+        .into_iter()
+        .chain(releases(&registry.join("cache"), name, ".crate"))
+        .collect::<Vec<_>>();
+    // unpacked sources win over archives of the same release
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.is_file().cmp(&b.1.is_file())));
+    candidates.into_iter().find_map(|(version, path)| {
+        let content = match path.is_dir() {
+            true => NAMES.iter().find_map(|v| read_to_string(path.join(v)).ok()),
+            false => from_archive(&path, &format!("{name}-{version}")),
+        }?;
+        Some((version, content))
+    })
+}
+
+/// `<name>-<version><suffix>` entries in the sub folders of `root`
+fn releases(root: &Path, name: &str, suffix: &str) -> Vec<(RustVersion, PathBuf)> {
+    let prefix = format!("{name}-");
+    let Ok(dirs) = read_dir(root) else {
+        return vec![];
+    };
+    dirs.filter_map(|v| v.ok())
+        .filter_map(|v| read_dir(v.path()).ok())
+        .flatten()
+        .filter_map(|v| v.ok())
+        .filter_map(|v| {
+            let file = v.file_name();
+            let version = file.to_str()?.strip_prefix(&prefix)?.strip_suffix(suffix)?;
+            Some((version_token(version)?, v.path()))
+        })
+        .collect()
+}
+
+fn from_archive(path: &Path, folder: &str) -> Option<String> {
+    let mut archive = Archive::new(GzDecoder::new(File::open(path).ok()?));
+    for entry in archive.entries().ok()? {
+        let Ok(mut entry) = entry else {
+            continue;
+        };
+        let Ok(entry_path) = entry.path() else {
+            continue;
+        };
+        let matches = entry_path
+            .strip_prefix(folder)
+            .ok()
+            .and_then(|v| v.to_str())
+            .is_some_and(|v| NAMES.contains(&v));
+        if matches {
+            let mut content = String::new();
+            entry.read_to_string(&mut content).ok()?;
+            return Some(content);
+        }
+    }
+    None
+}
+
+/// Sections of a markdown changelog for releases after `from` up to and including `to`,
+/// newest first like the changelog itself. Releases are recognized by a version in a heading
+pub fn changelog_excerpt(content: &str, from: &RustVersion, to: &RustVersion) -> Option<String> {
+    let mut out = vec![];
+    // level of the release headings and whether the current section is taken
+    let mut level = None;
+    let mut take = false;
+    let mut in_code = false;
+    for line in content.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        let heading = match in_code {
+            true => None,
+            false => heading(line),
+        };
+        if let Some((depth, title)) = heading {
+            match title.split_whitespace().find_map(version_token) {
+                Some(version) if level.is_none_or(|level| depth <= level) => {
+                    level = Some(depth);
+                    take = &version > from && &version <= to;
+                }
+                // `# Changelog` or a heading above the releases ends the section
+                _ if level.is_some_and(|level| depth <= level) => take = false,
+                _ => {}
+            }
+        }
+        if take {
+            out.push(line);
+        }
+    }
+    let out = out.join("\n");
+    match out.trim().is_empty() {
+        true => None,
+        false => Some(out.trim_end().to_owned()),
+    }
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let depth = line.chars().take_while(|v| *v == '#').count();
+    let title = line[depth..].strip_prefix(' ')?;
+    (depth > 0).then_some((depth, title))
+}
+
+/// `1.2.3`, `v1.2.3`, `[1.2.3]` or `1.2.3:`. Requires a dot so dates don't count
+fn version_token(token: &str) -> Option<RustVersion> {
+    let token = token
+        .trim_matches(|v: char| matches!(v, '[' | ']' | '(' | ')' | ':' | ',' | '`' | '*'))
+        .trim_start_matches('v');
+    if !token.contains('.') || !token.starts_with(|v: char| v.is_ascii_digit()) {
+        return None;
+    }
+    RustVersion::try_from(token).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_version::RustVersion;
+
+    use super::changelog_excerpt;
+
+    const CHANGELOG: &str = "# Changelog
+
+## [Unreleased]
+- wip
+
+## [1.4.0] - 2024-05-01
+### Added
+- new api
+
+## v1.3.0
+- fix
+
+## 1.2.0 (2023-01-01)
+- old
+";
+
+    fn ver(v: &str) -> RustVersion {
+        RustVersion::try_from(v).unwrap()
+    }
+
+    #[test]
+    fn excerpt() {
+        let out = changelog_excerpt(CHANGELOG, &ver("1.2.0"), &ver("1.4.0")).unwrap();
+        assert!(out.starts_with("## [1.4.0]"));
+        assert!(out.contains("### Added") && out.contains("- fix"));
+        assert!(!out.contains("wip") && !out.contains("old"));
+        assert!(changelog_excerpt(CHANGELOG, &ver("1.4.0"), &ver("1.4.0")).is_none());
+    }
+}
@@ -11,6 +11,8 @@ use tar::Archive;
 use tokio::time::sleep;
 use zstd::Decoder;
 
+use crate::progress::{percentage, Progress, ProgressTask};
+
 const OWNER: &str = "frederik-uni";
 const REPO: &str = "crates.io-dump-minfied";
 const ASSET_NAME: &str = "data.tar.zst";
@@ -50,7 +52,7 @@ fn check_lock_file(download_lock_file: &Path) -> bool {
     }
 }
 
-pub async fn download_update(root: &Path) -> Result<bool, anyhow::Error> {
+pub async fn download_update(root: &Path, progress: &Progress) -> Result<bool, anyhow::Error> {
     let download_lock_file = root.join(DOWNLOAD_LOCK_FILE);
 
     while check_lock_file(&download_lock_file) {
@@ -62,6 +64,8 @@ pub async fn download_update(root: &Path) -> Result<bool, anyhow::Error> {
     }
     File::create(&download_lock_file)?;
     let client = Client::new();
+    let task = progress.begin("Offline crate data", true);
+    task.report("Checking for updates", None);
 
     let url = format!(
         "https://api.github.com/repos/{}/{}/releases/latest",
@@ -81,6 +85,7 @@ pub async fn download_update(root: &Path) -> Result<bool, anyhow::Error> {
 
     if current_version.trim() == latest_version.trim() {
         fs::remove_file(&download_lock_file)?;
+        task.finish("Up to date");
         return Ok(false);
     }
 
@@ -95,7 +100,12 @@ pub async fn download_update(root: &Path) -> Result<bool, anyhow::Error> {
 
     let asset_path = tmp_dir.join(ASSET_NAME);
 
-    download(&client, &asset.browser_download_url, &asset_path).await?;
+    if !download(&client, &asset.browser_download_url, &asset_path, &task).await? {
+        fs::remove_dir_all(&tmp_dir)?;
+        fs::remove_file(&download_lock_file)?;
+        task.finish("Cancelled");
+        return Ok(false);
+    }
 
     let release_dir = Path::new(root).join(&release.tag_name);
     fs::rename(&tmp_dir.join("extracted"), &release_dir)?;
@@ -109,12 +119,19 @@ pub async fn download_update(root: &Path) -> Result<bool, anyhow::Error> {
     }
 
     fs::remove_file(download_lock_file)?;
+    task.finish(format!("Updated to {}", release.tag_name));
 
     Ok(true)
 }
 
-async fn download(client: &Client, url: &str, out_path: &Path) -> Result<(), anyhow::Error> {
-    let response = client
+/// Returns false if the task was cancelled
+async fn download(
+    client: &Client,
+    url: &str,
+    out_path: &Path,
+    task: &ProgressTask,
+) -> Result<bool, anyhow::Error> {
+    let mut response = client
         .get(url)
         .header("User-Agent", "rust-github-updater")
         .timeout(Duration::from_secs(15 * 60))
@@ -122,9 +139,29 @@ async fn download(client: &Client, url: &str, out_path: &Path) -> Result<(), any
         .await?
         .error_for_status()?;
 
+    let total = response.content_length();
+    let mut downloaded = 0;
+    let mut last_report = None;
     let mut out_file = File::create(out_path)?;
-    out_file.write_all(&response.bytes().await?.to_vec())?;
+    while let Some(chunk) = response.chunk().await? {
+        if task.is_cancelled() {
+            return Ok(false);
+        }
+        out_file.write_all(&chunk)?;
+        downloaded += chunk.len() as u64;
+        let percentage = total.and_then(|total| percentage(downloaded, total));
+        let report = (percentage, downloaded >> 20);
+        if last_report != Some(report) {
+            last_report = Some(report);
+            let message = match total {
+                Some(total) => format!("Downloading {}/{} MiB", downloaded >> 20, total >> 20),
+                None => format!("Downloading {} MiB", downloaded >> 20),
+            };
+            task.report(message, percentage);
+        }
+    }
 
+    task.report("Extracting", None);
     let file = File::open(out_path)?;
     let reader = BufReader::new(file);
     let decoder = Decoder::new(reader)?;
@@ -133,7 +170,12 @@ async fn download(client: &Client, url: &str, out_path: &Path) -> Result<(), any
     let extract_dir = out_path.parent().unwrap().join("extracted");
     fs::create_dir_all(&extract_dir)?;
 
-    archive.unpack(&extract_dir)?;
+    for entry in archive.entries()? {
+        if task.is_cancelled() {
+            return Ok(false);
+        }
+        entry?.unpack_in(&extract_dir)?;
+    }
 
-    Ok(())
+    Ok(true)
 }
@@ -0,0 +1,411 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use rust_version::VersionReq;
+
+use crate::{
+    api::{CacheItemOut, Root1},
+    InfoProvider,
+};
+
+/// What a feature request enables on a single release
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ResolvedFeatures {
+    /// Enabled features, including the implicit features of optional dependencies
+    pub features: BTreeSet<String>,
+    /// Optional dependencies that get activated
+    pub optional_deps: BTreeSet<String>,
+    /// Features turned on in dependencies via `crate/feat` and `crate?/feat`, keyed by the
+    /// dependency name as written in the manifest of the release
+    pub dep_features: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// A dependency of a release that ends up in the build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnabledDep {
+    /// Crate name, the dependency key can differ for renamed dependencies
+    pub package: String,
+    pub req: String,
+    pub features: BTreeSet<String>,
+    pub default_features: bool,
+}
+
+impl Root1 {
+    /// `features` and `features2` merged
+    fn feature_map(&self) -> HashMap<&str, &[String]> {
+        self.features
+            .iter()
+            .chain(self.features2.iter().flatten())
+            .map(|(k, v)| (k.as_str(), v.as_slice()))
+            .collect()
+    }
+
+    /// Expands the requested features like cargo does: `dep:x` only activates the optional
+    /// dependency, `x/feat` activates it and enables `feat` on it, `x?/feat` only enables
+    /// `feat` if `x` is active anyway. Optional dependencies that are never named with
+    /// `dep:` get an implicit feature of the same name
+    pub fn resolve_features<'a>(
+        &self,
+        features: impl IntoIterator<Item = &'a str>,
+        default_features: bool,
+    ) -> ResolvedFeatures {
+        let map = self.feature_map();
+        let optional = self
+            .deps
+            .iter()
+            .filter(|v| v.optional)
+            .map(|v| v.name.as_str())
+            .collect::<HashSet<_>>();
+        let explicit = map
+            .values()
+            .copied()
+            .flatten()
+            .filter_map(|v| v.strip_prefix("dep:"))
+            .collect::<HashSet<_>>();
+        let implicit = |name: &str| optional.contains(name) && !explicit.contains(name);
+
+        let mut out = ResolvedFeatures::default();
+        let mut weak = vec![];
+        let mut queue = features
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect::<VecDeque<_>>();
+        if default_features && map.contains_key("default") {
+            queue.push_back("default".to_owned());
+        }
+        while let Some(feature) = queue.pop_front() {
+            if let Some(dep) = feature.strip_prefix("dep:") {
+                out.optional_deps.insert(dep.to_owned());
+                continue;
+            }
+            if let Some((dep, feat)) = feature.split_once('/') {
+                if let Some(dep) = dep.strip_suffix('?') {
+                    weak.push((dep.to_owned(), feat.to_owned()));
+                    continue;
+                }
+                out.dep_features
+                    .entry(dep.to_owned())
+                    .or_default()
+                    .insert(feat.to_owned());
+                if optional.contains(dep) {
+                    out.optional_deps.insert(dep.to_owned());
+                    if implicit(dep) {
+                        queue.push_back(dep.to_owned());
+                    }
+                }
+                continue;
+            }
+            if out.features.contains(&feature) {
+                continue;
+            }
+            match map.get(feature.as_str()) {
+                Some(values) => queue.extend(values.iter().cloned()),
+                None if implicit(&feature) => {
+                    out.optional_deps.insert(feature.clone());
+                }
+                // unknown features are reported by the manifest diagnostics
+                None => continue,
+            }
+            out.features.insert(feature);
+        }
+        for (dep, feat) in weak {
+            if !optional.contains(dep.as_str()) || out.optional_deps.contains(&dep) {
+                out.dep_features.entry(dep).or_default().insert(feat);
+            }
+        }
+        out
+    }
+
+    /// Regular and build dependencies that are active with the resolved features
+    pub fn enabled_deps(&self, resolved: &ResolvedFeatures) -> Vec<EnabledDep> {
+        self.deps
+            .iter()
+            .filter(|v| v.kind.as_deref() != Some("dev"))
+            .filter(|v| !v.optional || resolved.optional_deps.contains(&v.name))
+            .map(|v| EnabledDep {
+                package: v.package.clone().unwrap_or_else(|| v.name.clone()),
+                req: v.req.clone(),
+                features: v
+                    .features
+                    .iter()
+                    .cloned()
+                    .chain(
+                        resolved
+                            .dep_features
+                            .get(&v.name)
+                            .into_iter()
+                            .flatten()
+                            .cloned(),
+                    )
+                    .collect(),
+                default_features: v.default_features,
+            })
+            .collect()
+    }
+}
+
+/// A requested feature that a newer release no longer has
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovedFeature {
+    pub name: String,
+    /// Most similar feature of the newer release
+    pub replacement: Option<String>,
+}
+
+impl Root1 {
+    /// Requested features this release knows and `target` doesn't. Features that are unknown
+    /// to both are left to the manifest diagnostics
+    pub fn removed_features<'a>(
+        &self,
+        target: &Root1,
+        requested: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<RemovedFeature> {
+        let current = self.feature_all();
+        let available = target.feature_all();
+        requested
+            .into_iter()
+            .filter(|v| current.iter().any(|c| c == v) && !available.iter().any(|a| a == v))
+            .map(|name| RemovedFeature {
+                name: name.to_owned(),
+                replacement: available
+                    .iter()
+                    .map(|v| (similarity(name, v), v))
+                    .filter(|(score, _)| *score >= 0.5)
+                    .max_by(|a, b| a.0.total_cmp(&b.0))
+                    .map(|(_, v)| v.clone()),
+            })
+            .collect()
+    }
+}
+
+/// Similarity of two feature names between 0 and 1. Takes the better of the edit distance
+/// and the overlap of the `-`/`_` separated words, so reordered names match too
+fn similarity(a: &str, b: &str) -> f32 {
+    let words = |v: &str| {
+        v.split(['-', '_'])
+            .filter(|v| !v.is_empty())
+            .map(str::to_lowercase)
+            .collect::<BTreeSet<_>>()
+    };
+    let (wa, wb) = (words(a), words(b));
+    let overlap = wa.intersection(&wb).count() as f32 / wa.union(&wb).count().max(1) as f32;
+    let len = a.chars().count().max(b.chars().count()).max(1);
+    let edit = 1.0 - levenshtein(a, b) as f32 / len as f32;
+    overlap.max(edit)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = match ca == *cb {
+                true => prev,
+                false => 1 + prev.min(row[j]).min(current),
+            };
+            prev = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// Features of every crate a release pulls in, unified per crate name like cargo does
+#[derive(Debug, Default, Clone)]
+pub struct FeatureTree {
+    pub root: ResolvedFeatures,
+    /// Picked version and enabled features of each crate in the tree
+    pub crates: BTreeMap<String, (String, BTreeSet<String>)>,
+    /// Crates whose index data wasn't available
+    pub missing: BTreeSet<String>,
+}
+
+struct Node {
+    release: Root1,
+    requested: BTreeSet<String>,
+    default_features: bool,
+    resolved: ResolvedFeatures,
+}
+
+impl InfoProvider {
+    /// Resolves the features of `release` and follows its active dependencies through the
+    /// index. Every crate gets the newest non-yanked release matching the first requirement
+    /// seen. Without `fetch` only cached index data is used
+    pub async fn resolve_feature_tree(
+        &self,
+        registry: Option<&str>,
+        release: &Root1,
+        features: &[String],
+        default_features: bool,
+        fetch: bool,
+    ) -> FeatureTree {
+        const LIMIT: usize = 500;
+        let root = release.resolve_features(features.iter().map(String::as_str), default_features);
+        let mut queue = release
+            .enabled_deps(&root)
+            .into_iter()
+            .collect::<VecDeque<_>>();
+        let mut nodes: BTreeMap<String, Node> = BTreeMap::new();
+        let mut missing = BTreeSet::new();
+        while let Some(dep) = queue.pop_front() {
+            if let Some(node) = nodes.get_mut(&dep.package) {
+                let default_features = node.default_features || dep.default_features;
+                if dep.features.is_subset(&node.requested)
+                    && default_features == node.default_features
+                {
+                    continue;
+                }
+                node.requested.extend(dep.features);
+                node.default_features = default_features;
+                node.resolved = node.release.resolve_features(
+                    node.requested.iter().map(String::as_str),
+                    node.default_features,
+                );
+                queue.extend(node.release.enabled_deps(&node.resolved));
+                continue;
+            }
+            if nodes.len() >= LIMIT || missing.contains(&dep.package) {
+                continue;
+            }
+            let versions = match fetch {
+                true => self.get_info(registry, &dep.package).await.ok(),
+                false => match self.get_info_cache(registry, &dep.package).await {
+                    CacheItemOut::Ready(v) => Some(v),
+                    _ => None,
+                },
+            };
+            let req = VersionReq::try_from(dep.req.as_str()).ok();
+            let Some(release) = versions.and_then(|versions| {
+                versions.into_iter().rev().find(|v| {
+                    !v.yanked
+                        && match (&req, v.ver()) {
+                            (Some(req), Some(ver)) => req.matches(&ver),
+                            _ => false,
+                        }
+                })
+            }) else {
+                missing.insert(dep.package);
+                continue;
+            };
+            let resolved = release.resolve_features(
+                dep.features.iter().map(String::as_str),
+                dep.default_features,
+            );
+            queue.extend(release.enabled_deps(&resolved));
+            nodes.insert(
+                dep.package,
+                Node {
+                    release,
+                    requested: dep.features,
+                    default_features: dep.default_features,
+                    resolved,
+                },
+            );
+        }
+        FeatureTree {
+            root,
+            crates: nodes
+                .into_iter()
+                .map(|(k, v)| (k, (v.release.vers, v.resolved.features)))
+                .collect(),
+            missing,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap};
+
+    use crate::api::{Deps1, Root1};
+
+    fn release() -> Root1 {
+        let optional = |name, req| Deps1 {
+            optional: true,
+            ..Deps1::new(name, req)
+        };
+        let features = |items: &[(&str, &[&str])]| {
+            items
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.iter().map(|v| v.to_string()).collect()))
+                .collect::<HashMap<_, _>>()
+        };
+        Root1 {
+            deps: vec![
+                optional("serde", "^1"),
+                optional("tokio", "^1"),
+                Deps1::new("log", "^0.4"),
+                Deps1 {
+                    package: Some("serde_json".to_owned()),
+                    default_features: false,
+                    ..optional("json", "^1")
+                },
+            ],
+            features: features(&[
+                ("default", &["std"]),
+                ("std", &["log/std", "serde?/std"]),
+                ("full", &["dep:tokio", "tokio/rt", "derive"]),
+                ("derive", &["serde/derive"]),
+            ]),
+            features2: Some(features(&[("json", &["dep:json"])])),
+            ..Root1::new("demo", "1.0.0")
+        }
+    }
+
+    fn set(items: &[&str]) -> BTreeSet<String> {
+        items.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn defaults_and_weak() {
+        let resolved = release().resolve_features([], true);
+        assert_eq!(resolved.features, set(&["default", "std"]));
+        assert!(resolved.optional_deps.is_empty());
+        // serde isn't active, so `serde?/std` does nothing
+        assert_eq!(resolved.dep_features.get("log"), Some(&set(&["std"])));
+        assert!(!resolved.dep_features.contains_key("serde"));
+    }
+
+    #[test]
+    fn optional_deps() {
+        let resolved = release().resolve_features(["full", "json"], true);
+        assert_eq!(resolved.optional_deps, set(&["json", "serde", "tokio"]));
+        // serde has no `dep:` so it keeps its implicit feature, tokio doesn't
+        assert_eq!(
+            resolved.features,
+            set(&["default", "derive", "full", "json", "serde", "std"])
+        );
+        assert_eq!(
+            resolved.dep_features.get("serde"),
+            Some(&set(&["derive", "std"]))
+        );
+        let deps = release().enabled_deps(&resolved);
+        assert!(deps
+            .iter()
+            .any(|v| v.package == "serde_json" && !v.default_features));
+        assert_eq!(deps.len(), 4);
+    }
+
+    #[test]
+    fn removed() {
+        let mut target = release();
+        target.features.remove("full");
+        target.features.remove("derive");
+        target
+            .features
+            .insert("serde-derive".to_owned(), vec!["serde/derive".to_owned()]);
+        let removed = release().removed_features(&target, ["std", "derive", "full", "nope"]);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].name, "derive");
+        assert_eq!(removed[0].replacement.as_deref(), Some("serde-derive"));
+        assert_eq!(removed[1].replacement, None);
+    }
+
+    #[test]
+    fn no_default_features() {
+        let resolved = release().resolve_features(["derive"], false);
+        assert_eq!(resolved.features, set(&["derive", "serde"]));
+        assert_eq!(resolved.optional_deps, set(&["serde"]));
+    }
+}
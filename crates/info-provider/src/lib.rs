@@ -1,17 +1,26 @@
 use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
+use advisory::AdvisoryDb;
 use api::{CacheItem, CacheItemOut, Crate, Root1};
 use fst::{Set, SetBuilder};
 use local::OfflineCrate;
+use progress::Progress;
+use source::ArchiveKey;
 use tokio::sync::RwLock;
 
+pub mod advisory;
 pub mod api;
+pub mod changelog;
 mod downloader;
+pub mod features;
 mod local;
+pub mod progress;
+pub mod resolver;
+pub mod source;
 pub struct InfoProvider {
     client: Arc<reqwest::Client>,
     registry: &'static str,
-    registries: HashMap<String, String>,
+    registries: std::sync::RwLock<HashMap<String, String>>,
     readme_cache: Arc<RwLock<HashMap<(String, String), CacheItem<String>>>>,
     info_cache: Arc<RwLock<HashMap<String, HashMap<String, CacheItem<Root1>>>>>,
     search_cache: Arc<RwLock<HashMap<String, CacheItem<Crate>>>>,
@@ -25,6 +34,9 @@ pub struct InfoProvider {
         )>,
     >,
     root: PathBuf,
+    progress: Progress,
+    advisories: RwLock<AdvisoryDb>,
+    checksums: Arc<RwLock<HashMap<ArchiveKey, String>>>,
 }
 
 impl InfoProvider {
@@ -37,26 +49,18 @@ impl InfoProvider {
             HashMap::new(),
             Vec::new(),
         )));
+        let progress = Progress::default();
         if offline {
-            local::init(off.clone(), off_data.clone(), data_path.clone()).await;
+            local::init(
+                off.clone(),
+                off_data.clone(),
+                data_path.clone(),
+                progress.clone(),
+            )
+            .await;
         }
 
-        let registries = cargo_config2::Config::load()
-            .expect("missing cargo config")
-            .registries
-            .into_iter()
-            .flat_map(|(name, registry)| {
-                registry.index.map(|index| {
-                    (
-                        name,
-                        index
-                            .strip_prefix("sparse+")
-                            .map(ToOwned::to_owned)
-                            .unwrap_or(index),
-                    )
-                })
-            })
-            .collect();
+        let registries = load_registries().expect("missing cargo config");
 
         Self {
             root: data_path,
@@ -64,11 +68,14 @@ impl InfoProvider {
             offline: off,
             client: Arc::new(reqwest::Client::new()),
             registry: "https://index.crates.io/",
-            registries,
+            registries: std::sync::RwLock::new(registries),
             info_cache: Default::default(),
             search_cache: Default::default(),
             readme_cache: Default::default(),
             per_page: RwLock::new(per_page),
+            progress,
+            advisories: Default::default(),
+            checksums: Default::default(),
         }
     }
 
@@ -91,12 +98,29 @@ impl InfoProvider {
             }
             (false, true) => {
                 *self.offline.write().await = true;
-                local::init(self.offline.clone(), self.data.clone(), self.root.clone()).await;
+                local::init(
+                    self.offline.clone(),
+                    self.data.clone(),
+                    self.root.clone(),
+                    self.progress.clone(),
+                )
+                .await;
             }
             _ => {}
         }
     }
 
+    /// Re-reads the registries from the cargo config, e.g. after `.cargo/config.toml` changed
+    pub async fn reload_registries(&self) {
+        if let Some(registries) = load_registries() {
+            *self.registries.write().unwrap() = registries;
+        }
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
     pub async fn get_info_cache(&self, registry: Option<&str>, name: &str) -> CacheItemOut<Root1> {
         self.get_info_cache_api(registry, name).await
     }
@@ -121,6 +145,26 @@ impl InfoProvider {
     }
 }
 
+fn load_registries() -> Option<HashMap<String, String>> {
+    let registries = cargo_config2::Config::load()
+        .ok()?
+        .registries
+        .into_iter()
+        .flat_map(|(name, registry)| {
+            registry.index.map(|index| {
+                (
+                    name,
+                    index
+                        .strip_prefix("sparse+")
+                        .map(ToOwned::to_owned)
+                        .unwrap_or(index),
+                )
+            })
+        })
+        .collect();
+    Some(registries)
+}
+
 #[cfg(test)]
 mod tests {
     use std::{path::PathBuf, time::Duration};
@@ -11,7 +11,12 @@ use byteorder::{LittleEndian, ReadBytesExt};
 
 use tokio::{sync::RwLock, time::sleep};
 
-use crate::{api::Crate, downloader::download_update, InfoProvider};
+use crate::{
+    api::Crate,
+    downloader::download_update,
+    progress::{percentage, Progress, ProgressTask},
+    InfoProvider,
+};
 
 impl InfoProvider {
     pub async fn search_local(&self, name: &str) -> Vec<Crate> {
@@ -45,13 +50,14 @@ pub async fn init(
         )>,
     >,
     root: PathBuf,
+    progress: Progress,
 ) {
     {
-        if let Some(v) = read_data(&root.join("offline")) {
+        if let Some(v) = read_data(&root.join("offline"), &progress) {
             *data.write().await = v;
         }
     }
-    updater(offline, root.join("offline"), data);
+    updater(offline, root.join("offline"), data, progress);
 }
 
 use fst::{IntoStreamer, Set, SetBuilder, Streamer as _};
@@ -64,13 +70,25 @@ fn tokenize(name: &str) -> Vec<String> {
         .collect()
 }
 
+/// Returns None if the task was cancelled
 fn build_index(
     crate_list: Vec<Arc<OfflineCrate>>,
-) -> (Set<Vec<u8>>, HashMap<String, Vec<Arc<OfflineCrate>>>) {
+    task: &ProgressTask,
+) -> Option<(Set<Vec<u8>>, HashMap<String, Vec<Arc<OfflineCrate>>>)> {
     let mut token_to_crates: HashMap<String, Vec<Arc<OfflineCrate>>> = HashMap::new();
     let mut unique_tokens = HashSet::new();
 
-    for krate_arc in crate_list {
+    let total = crate_list.len() as u64;
+    let mut last_percentage = None;
+    for (i, krate_arc) in crate_list.into_iter().enumerate() {
+        if task.is_cancelled() {
+            return None;
+        }
+        let percentage = percentage(i as u64, total);
+        if percentage != last_percentage {
+            last_percentage = percentage;
+            task.report(format!("Indexing {i}/{total} crates"), percentage);
+        }
         let tokens = tokenize(&krate_arc.name);
         for token in tokens {
             let token = token.to_lowercase();
@@ -97,7 +115,7 @@ fn build_index(
     };
 
     let set = Set::new(buffer).unwrap();
-    (set, token_to_crates)
+    Some((set, token_to_crates))
 }
 
 fn search(
@@ -147,6 +165,7 @@ fn search(
 
 fn read_data(
     root: &Path,
+    progress: &Progress,
 ) -> Option<(
     Set<Vec<u8>>,
     HashMap<String, Vec<Arc<OfflineCrate>>>,
@@ -197,7 +216,9 @@ fn read_data(
             res.push(OfflineCrate::from_vec(buffer, &keywords, &categories))
         }
         let items = res.into_iter().map(Arc::new).collect::<Vec<_>>();
-        let indexed = build_index(items.clone());
+        let task = progress.begin("Indexing offline crate data", true);
+        let indexed = build_index(items.clone(), &task)?;
+        task.finish(format!("Indexed {} crates", items.len()));
         return Some((indexed.0, indexed.1, items));
     }
     None
@@ -213,14 +234,15 @@ pub fn updater(
             Vec<Arc<OfflineCrate>>,
         )>,
     >,
+    progress: Progress,
 ) {
     tokio::spawn(async move {
         loop {
             if !*offline.read().await {
                 break;
             }
-            if download_update(&root).await.unwrap_or_default() {
-                if let Some(v) = read_data(&root) {
+            if download_update(&root, &progress).await.unwrap_or_default() {
+                if let Some(v) = read_data(&root, &progress) {
                     *data.write().await = v;
                 }
             }
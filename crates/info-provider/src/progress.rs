@@ -0,0 +1,122 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Long running work, forwarded to the client as work done progress
+#[derive(Debug, Clone)]
+pub enum ProgressEvent {
+    Begin {
+        token: String,
+        title: String,
+        cancellable: bool,
+    },
+    Report {
+        token: String,
+        message: Option<String>,
+        /// 0 - 100
+        percentage: Option<u32>,
+    },
+    End {
+        token: String,
+        message: Option<String>,
+    },
+}
+
+#[derive(Clone, Default)]
+pub struct Progress {
+    sender: Arc<Mutex<Option<UnboundedSender<ProgressEvent>>>>,
+    running: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Progress {
+    /// Events are dropped until someone subscribes
+    pub fn subscribe(&self) -> UnboundedReceiver<ProgressEvent> {
+        let (tx, rx) = unbounded_channel();
+        *self.sender.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    pub fn begin(&self, title: &str, cancellable: bool) -> ProgressTask {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let token = format!("cargotom/{id}");
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.running
+            .lock()
+            .unwrap()
+            .insert(token.clone(), cancelled.clone());
+        self.send(ProgressEvent::Begin {
+            token: token.clone(),
+            title: title.to_owned(),
+            cancellable,
+        });
+        ProgressTask {
+            token,
+            progress: self.clone(),
+            cancelled,
+            message: None,
+        }
+    }
+
+    /// Marks the task as cancelled, the task itself decides when to stop
+    pub fn cancel(&self, token: &str) {
+        if let Some(v) = self.running.lock().unwrap().get(token) {
+            v.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn send(&self, event: ProgressEvent) {
+        if let Some(sender) = self.sender.lock().unwrap().as_ref() {
+            let _ = sender.send(event);
+        }
+    }
+}
+
+/// Handle for a running task. Sends the end event when dropped
+pub struct ProgressTask {
+    token: String,
+    progress: Progress,
+    cancelled: Arc<AtomicBool>,
+    message: Option<String>,
+}
+
+impl ProgressTask {
+    pub fn report(&self, message: impl Into<String>, percentage: Option<u32>) {
+        self.progress.send(ProgressEvent::Report {
+            token: self.token.clone(),
+            message: Some(message.into()),
+            percentage: percentage.map(|v| v.min(100)),
+        });
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn finish(mut self, message: impl Into<String>) {
+        self.message = Some(message.into());
+    }
+}
+
+impl Drop for ProgressTask {
+    fn drop(&mut self) {
+        self.progress.running.lock().unwrap().remove(&self.token);
+        self.progress.send(ProgressEvent::End {
+            token: self.token.clone(),
+            message: self.message.take(),
+        });
+    }
+}
+
+pub fn percentage(done: u64, total: u64) -> Option<u32> {
+    match total {
+        0 => None,
+        total => Some((done.min(total) * 100 / total) as u32),
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use rust_version::{RustVersion, VersionReq};
+use serde::Serialize;
+
+use crate::{
+    api::{CacheItemOut, Root1},
+    features::ResolvedFeatures,
+    InfoProvider,
+};
+
+/// A `[[package]]` of the current lock file, `dependencies` index into the same list
+#[derive(Debug, Clone)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    /// None for workspace members and path dependencies
+    pub source: Option<String>,
+    pub dependencies: Vec<usize>,
+}
+
+impl LockedPackage {
+    fn is_crates_io(&self) -> bool {
+        matches!(
+            self.source.as_deref(),
+            Some(
+                "registry+https://github.com/rust-lang/crates.io-index"
+                    | "sparse+https://index.crates.io/"
+            )
+        )
+    }
+}
+
+/// A member changing the requirement of one of its dependencies
+#[derive(Debug, Clone)]
+pub struct Upgrade {
+    /// Index of the member in the locked packages
+    pub member: usize,
+    /// Crate name of the dependency
+    pub name: String,
+    pub requirement: String,
+    pub features: Vec<String>,
+    pub default_features: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct PackageVersion {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PackageChange {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// What changing a requirement would do to the lock file
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpgradeImpact {
+    pub added: Vec<PackageVersion>,
+    pub removed: Vec<PackageVersion>,
+    pub changed: Vec<PackageChange>,
+    /// Crates that end up locked at several versions and weren't before
+    pub new_duplicates: Vec<String>,
+    /// Requirements of locked packages that keep a semver compatible version from unifying
+    pub conflicts: Vec<String>,
+    /// Releases that need a newer Rust than the package declares
+    pub msrv: Vec<String>,
+    /// Crates without index data, the simulation doesn't follow them
+    pub missing: BTreeSet<String>,
+}
+
+struct Node {
+    name: String,
+    version: String,
+    /// Index entry of packages the simulation added
+    release: Option<Root1>,
+}
+
+/// The lock graph with the simulated changes applied. Nodes past `locked` are new
+struct Simulation<'a> {
+    locked: &'a [LockedPackage],
+    nodes: Vec<Node>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl<'a> Simulation<'a> {
+    fn new(locked: &'a [LockedPackage]) -> Self {
+        Self {
+            locked,
+            nodes: locked
+                .iter()
+                .map(|v| Node {
+                    name: v.name.clone(),
+                    version: v.version.clone(),
+                    release: None,
+                })
+                .collect(),
+            edges: locked.iter().map(|v| v.dependencies.clone()).collect(),
+        }
+    }
+
+    fn add(&mut self, release: Root1) -> usize {
+        self.nodes.push(Node {
+            name: release.name.clone(),
+            version: release.vers.clone(),
+            release: Some(release),
+        });
+        self.edges.push(vec![]);
+        self.nodes.len() - 1
+    }
+
+    /// Highest package already in the graph that satisfies the requirement, cargo keeps
+    /// locked versions when it can
+    fn best(&self, name: &str, req: &VersionReq) -> Option<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.name == name)
+            .filter_map(|(i, v)| Some((i, RustVersion::try_from(v.version.as_str()).ok()?)))
+            .filter(|(_, v)| req.matches(v))
+            .max_by(|a, b| a.1.cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    fn reachable(&self, edges: &[Vec<usize>]) -> BTreeSet<usize> {
+        let mut seen = BTreeSet::new();
+        let mut queue = self
+            .locked
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.source.is_none())
+            .map(|(i, _)| i)
+            .collect::<VecDeque<_>>();
+        while let Some(index) = queue.pop_front() {
+            if seen.insert(index) {
+                queue.extend(edges[index].iter().copied());
+            }
+        }
+        seen
+    }
+
+    fn finish(&self, msrv: Option<&RustVersion>, mut impact: UpgradeImpact) -> UpgradeImpact {
+        let original = self
+            .locked
+            .iter()
+            .map(|v| v.dependencies.clone())
+            .collect::<Vec<_>>();
+        let before = self.reachable(&original);
+        let after = self.reachable(&self.edges);
+        let package = |i: &usize| PackageVersion {
+            name: self.nodes[*i].name.clone(),
+            version: self.nodes[*i].version.clone(),
+        };
+        let mut removed = before
+            .difference(&after)
+            .map(package)
+            .collect::<BTreeSet<_>>();
+        let mut added = after
+            .iter()
+            .filter(|v| **v >= self.locked.len())
+            .map(package)
+            .collect::<BTreeSet<_>>();
+        for old in removed.clone() {
+            let same = added
+                .iter()
+                .filter(|v| v.name == old.name)
+                .cloned()
+                .collect::<Vec<_>>();
+            if let [new] = same.as_slice() {
+                if removed.iter().filter(|v| v.name == old.name).count() == 1 {
+                    removed.remove(&old);
+                    added.remove(new);
+                    impact.changed.push(PackageChange {
+                        name: old.name,
+                        from: old.version,
+                        to: new.version.clone(),
+                    });
+                }
+            }
+        }
+        let versions = |set: &BTreeSet<usize>| {
+            let mut out: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+            for i in set {
+                out.entry(&self.nodes[*i].name)
+                    .or_default()
+                    .insert(&self.nodes[*i].version);
+            }
+            out
+        };
+        let (old, new) = (versions(&before), versions(&after));
+        impact.new_duplicates = new
+            .iter()
+            .filter(|(name, v)| v.len() > 1 && old.get(*name).map_or(0, |v| v.len()) < v.len())
+            .map(|(name, v)| {
+                format!(
+                    "{name} ({})",
+                    v.iter().copied().collect::<Vec<_>>().join(", ")
+                )
+            })
+            .collect();
+        for i in after.iter().filter(|v| **v >= self.locked.len()) {
+            let Some(release) = &self.nodes[*i].release else {
+                continue;
+            };
+            if let (false, Some(required), Some(msrv)) = (
+                release.supports_rust(msrv),
+                release.rust_version.as_ref(),
+                msrv,
+            ) {
+                impact.msrv.push(format!(
+                    "{} {} needs Rust {required}, the package declares {msrv}",
+                    release.name, release.vers
+                ));
+            }
+        }
+        impact.added = added.into_iter().collect();
+        impact.removed = removed.into_iter().collect();
+        impact
+    }
+}
+
+/// Cargo allows one version per semver compatible range: same major, or same minor for
+/// `0.x`, or same patch for `0.0.x`
+fn compatible(a: &str, b: &str) -> bool {
+    let (Ok(a), Ok(b)) = (RustVersion::try_from(a), RustVersion::try_from(b)) else {
+        return false;
+    };
+    match (a.mahor(), b.mahor()) {
+        (Some(0), Some(0)) => match (a.minor(), b.minor()) {
+            (Some(0), Some(0)) => a.patch() == b.patch(),
+            (a, b) => a == b,
+        },
+        (a, b) => a == b,
+    }
+}
+
+fn newest(versions: Vec<Root1>, req: &VersionReq) -> Option<Root1> {
+    versions
+        .into_iter()
+        .rev()
+        .find(|v| !v.yanked && v.ver().is_some_and(|v| req.matches(&v)))
+}
+
+impl InfoProvider {
+    /// Simulates a member changing the requirement of a dependency against the current lock
+    /// file. Like cargo, locked packages are kept where their version still fits, new ones get
+    /// the newest matching release and semver compatible versions are unified. Only normal and
+    /// build dependencies are followed, features of packages already locked aren't re-resolved
+    pub async fn simulate_upgrade(
+        &self,
+        registry: Option<&str>,
+        locked: &[LockedPackage],
+        upgrade: &Upgrade,
+        msrv: Option<&RustVersion>,
+    ) -> UpgradeImpact {
+        const LIMIT: usize = 300;
+        let mut impact = UpgradeImpact::default();
+        let mut sim = Simulation::new(locked);
+        let Ok(req) = VersionReq::try_from(upgrade.requirement.as_str()) else {
+            return impact;
+        };
+        let Some(member) = locked.get(upgrade.member) else {
+            return impact;
+        };
+        let old = member
+            .dependencies
+            .iter()
+            .copied()
+            .find(|v| locked[*v].name == upgrade.name);
+        let fits = old
+            .and_then(|v| RustVersion::try_from(locked[v].version.as_str()).ok())
+            .is_some_and(|v| req.matches(&v));
+        if fits {
+            return impact;
+        }
+        let Some(release) = self.release(registry, &upgrade.name, &req).await else {
+            impact.missing.insert(upgrade.name.clone());
+            return impact;
+        };
+        let resolved = release.resolve_features(
+            upgrade.features.iter().map(String::as_str),
+            upgrade.default_features,
+        );
+        let root = sim.add(release);
+        sim.edges[upgrade.member].retain(|v| Some(*v) != old);
+        sim.edges[upgrade.member].push(root);
+        self.unify(&mut sim, root, &mut impact).await;
+        let mut queue = VecDeque::from([(root, resolved)]);
+        while let Some((index, resolved)) = queue.pop_front() {
+            let Some(release) = sim.nodes[index].release.clone() else {
+                continue;
+            };
+            for dep in release.enabled_deps(&resolved) {
+                let Ok(req) = VersionReq::try_from(dep.req.as_str()) else {
+                    continue;
+                };
+                if let Some(existing) = sim.best(&dep.package, &req) {
+                    sim.edges[index].push(existing);
+                    continue;
+                }
+                if sim.nodes.len() - locked.len() >= LIMIT {
+                    continue;
+                }
+                let Some(release) = self.release(registry, &dep.package, &req).await else {
+                    impact.missing.insert(dep.package);
+                    continue;
+                };
+                let resolved: ResolvedFeatures = release.resolve_features(
+                    dep.features.iter().map(String::as_str),
+                    dep.default_features,
+                );
+                let node = sim.add(release);
+                sim.edges[index].push(node);
+                self.unify(&mut sim, node, &mut impact).await;
+                queue.push_back((node, resolved));
+            }
+        }
+        sim.finish(msrv, impact)
+    }
+
+    async fn release(&self, registry: Option<&str>, name: &str, req: &VersionReq) -> Option<Root1> {
+        newest(self.get_info(registry, name).await.ok()?, req)
+    }
+
+    /// Moves dependents of semver compatible copies of a new package over to it when their
+    /// requirement allows it, and reports the ones that don't
+    async fn unify(&self, sim: &mut Simulation<'_>, node: usize, impact: &mut UpgradeImpact) {
+        let name = sim.nodes[node].name.clone();
+        let version = sim.nodes[node].version.clone();
+        let Ok(new) = RustVersion::try_from(version.as_str()) else {
+            return;
+        };
+        let others = (0..sim.nodes.len())
+            .filter(|v| *v != node && sim.nodes[*v].name == name)
+            .filter(|v| compatible(&sim.nodes[*v].version, &version))
+            .collect::<Vec<_>>();
+        for other in others {
+            for parent in 0..sim.edges.len() {
+                if !sim.edges[parent].contains(&other) {
+                    continue;
+                }
+                let req = self.requirement(sim, parent, &name).await;
+                match req
+                    .as_ref()
+                    .and_then(|v| VersionReq::try_from(v.as_str()).ok())
+                {
+                    Some(parsed) if !parsed.matches(&new) => impact.conflicts.push(format!(
+                        "{} {} requires {name} {}",
+                        sim.nodes[parent].name,
+                        sim.nodes[parent].version,
+                        req.unwrap_or_default()
+                    )),
+                    // members and packages without index data are assumed to allow it
+                    _ => {
+                        for edge in &mut sim.edges[parent] {
+                            if *edge == other {
+                                *edge = node;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Requirement of a package in the graph on the crate `name`, from the index of the
+    /// registry the package is locked from
+    async fn requirement(&self, sim: &Simulation<'_>, parent: usize, name: &str) -> Option<String> {
+        let release = match &sim.nodes[parent].release {
+            Some(release) => release.clone(),
+            None => {
+                let locked = sim.locked.get(parent)?;
+                let registry = match locked.is_crates_io() {
+                    true => None,
+                    false => Some(self.registry_name(locked.source.as_deref()?)?),
+                };
+                let registry = registry.as_deref();
+                let versions = match self.get_info_cache(registry, &locked.name).await {
+                    CacheItemOut::Ready(v) => v,
+                    _ => self.get_info(registry, &locked.name).await.ok()?,
+                };
+                versions.into_iter().find(|v| v.vers == locked.version)?
+            }
+        };
+        release
+            .deps
+            .iter()
+            .filter(|v| v.kind.as_deref() != Some("dev"))
+            .find(|v| v.package.as_ref().unwrap_or(&v.name) == name)
+            .map(|v| v.req.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compatible, LockedPackage, Simulation, UpgradeImpact};
+    use crate::api::Root1;
+
+    fn locked(name: &str, version: &str, dependencies: Vec<usize>) -> LockedPackage {
+        LockedPackage {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            source: (name != "app").then(|| "sparse+https://index.crates.io/".to_owned()),
+            dependencies,
+        }
+    }
+
+    fn release(name: &str, vers: &str) -> Root1 {
+        Root1 {
+            rust_version: Some("1.80".to_owned()),
+            ..Root1::new(name, vers)
+        }
+    }
+
+    #[test]
+    fn semver_compatible() {
+        assert!(compatible("1.2.0", "1.9.3"));
+        assert!(!compatible("1.2.0", "2.0.0"));
+        assert!(compatible("0.3.1", "0.3.9"));
+        assert!(!compatible("0.3.1", "0.4.0"));
+        assert!(!compatible("0.0.1", "0.0.2"));
+    }
+
+    #[test]
+    fn finish() {
+        // app -> a 1.0 -> b 1.0, app -> c 1.0 -> b 1.0
+        let lock = vec![
+            locked("app", "0.1.0", vec![1, 3]),
+            locked("a", "1.0.0", vec![2]),
+            locked("b", "1.0.0", vec![]),
+            locked("c", "1.0.0", vec![2]),
+        ];
+        let mut sim = Simulation::new(&lock);
+        let a = sim.add(release("a", "2.0.0"));
+        let b = sim.add(release("b", "2.0.0"));
+        sim.edges[0] = vec![a, 3];
+        sim.edges[a] = vec![b];
+        let rust = rust_version::RustVersion::try_from("1.70").unwrap();
+        let impact = sim.finish(Some(&rust), UpgradeImpact::default());
+        assert_eq!(impact.changed.len(), 1);
+        assert_eq!(impact.changed[0].name, "a");
+        assert_eq!(impact.added.len(), 1);
+        assert!(impact.removed.is_empty());
+        assert_eq!(impact.new_duplicates, vec!["b (1.0.0, 2.0.0)".to_owned()]);
+        assert_eq!(impact.msrv.len(), 2);
+    }
+}
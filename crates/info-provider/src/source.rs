@@ -0,0 +1,170 @@
+use std::{
+    env,
+    fs::{metadata, read, read_dir, read_to_string},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use openssl::sha::sha256;
+
+use crate::InfoProvider;
+
+pub fn cargo_home() -> Option<PathBuf> {
+    if let Some(home) = env::var_os("CARGO_HOME") {
+        return Some(PathBuf::from(home));
+    }
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".cargo"))
+}
+
+/// Finds the unpacked source of a crate in `$CARGO_HOME/registry/src`
+pub fn registry_source(name: &str, version: &str) -> Option<PathBuf> {
+    let src = cargo_home()?.join("registry").join("src");
+    let folder = format!("{name}-{version}");
+    read_dir(src)
+        .ok()?
+        .filter_map(|v| v.ok())
+        .map(|v| v.path().join(&folder))
+        .find(|v| v.is_dir())
+}
+
+/// Archive path, modification time and size of a hashed `.crate` file
+pub(crate) type ArchiveKey = (PathBuf, SystemTime, u64);
+
+/// The downloaded `.crate` archive in `$CARGO_HOME/registry/cache`
+fn crate_archive(name: &str, version: &str) -> Option<PathBuf> {
+    let cache = cargo_home()?.join("registry").join("cache");
+    let file = format!("{name}-{version}.crate");
+    read_dir(cache)
+        .ok()?
+        .filter_map(|v| v.ok())
+        .filter(|v| {
+            let dir = v.file_name();
+            let dir = dir.to_string_lossy();
+            dir.starts_with("index.crates.io-") || dir.starts_with("github.com-")
+        })
+        .map(|v| v.path().join(&file))
+        .find(|v| v.is_file())
+}
+
+impl InfoProvider {
+    /// Sha256 of the downloaded `.crate` archives of `(name, version)` pairs, as hex. Hashes
+    /// are kept until the archive is downloaded again. All of it runs in one blocking task
+    pub async fn crate_checksums(&self, crates: Vec<(String, String)>) -> Vec<Option<String>> {
+        let cache = self.checksums.clone();
+        tokio::task::spawn_blocking(move || {
+            crates
+                .iter()
+                .map(|(name, version)| {
+                    let path = crate_archive(name, version)?;
+                    let meta = metadata(&path).ok()?;
+                    let key = (path, meta.modified().ok()?, meta.len());
+                    if let Some(v) = cache.blocking_read().get(&key) {
+                        return Some(v.clone());
+                    }
+                    let hash = sha256(&read(&key.0).ok()?)
+                        .iter()
+                        .map(|v| format!("{v:02x}"))
+                        .collect::<String>();
+                    cache.blocking_write().insert(key, hash.clone());
+                    Some(hash)
+                })
+                .collect()
+        })
+        .await
+        .unwrap_or_default()
+    }
+}
+
+/// Finds a git dependency in `$CARGO_HOME/git/checkouts/<repo>-<hash>/<short rev>`.
+/// Repositories holding several crates are searched for the package's manifest
+pub fn git_checkout(name: &str, rev: &str) -> Option<PathBuf> {
+    let short = rev.get(..7)?;
+    let checkouts = cargo_home()?.join("git").join("checkouts");
+    read_dir(checkouts)
+        .ok()?
+        .filter_map(|v| v.ok())
+        .map(|v| v.path().join(short))
+        .filter(|v| v.is_dir())
+        .find_map(|v| find_package(&v, name, 3))
+}
+
+fn find_package(dir: &Path, name: &str, depth: usize) -> Option<PathBuf> {
+    if manifest_name(&dir.join("Cargo.toml")).as_deref() == Some(name) {
+        return Some(dir.to_path_buf());
+    }
+    if depth == 0 {
+        return None;
+    }
+    read_dir(dir)
+        .ok()?
+        .filter_map(|v| v.ok())
+        .filter(|v| v.path().is_dir())
+        .filter(|v| !matches!(v.file_name().to_str(), Some("target" | ".git")))
+        .find_map(|v| find_package(&v.path(), name, depth - 1))
+}
+
+/// `name` of the `[package]` table
+fn manifest_name(path: &Path) -> Option<String> {
+    manifest_value(path, "package", "name")
+}
+
+/// A string `key` of a top level table, without a full toml parse
+fn manifest_value(path: &Path, table: &str, key: &str) -> Option<String> {
+    let content = read_to_string(path).ok()?;
+    let header = format!("[{table}]");
+    let mut in_table = false;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_table = line == header;
+            continue;
+        }
+        let Some((k, value)) = line.split_once('=') else {
+            continue;
+        };
+        if in_table && k.trim() == key {
+            return Some(value.trim().trim_matches('"').to_owned());
+        }
+    }
+    None
+}
+
+/// Crate level `//!` docs of the library target followed by the README, either may be
+/// missing. The library is found through `[lib] path`, `src/lib.rs` by default
+pub fn local_docs(dir: &Path) -> Vec<String> {
+    let lib = manifest_value(&dir.join("Cargo.toml"), "lib", "path")
+        .unwrap_or_else(|| "src/lib.rs".to_owned());
+    let lib = read_to_string(dir.join(lib)).unwrap_or_default();
+    let docs = lib
+        .lines()
+        .map(str::trim_start)
+        .skip_while(|v| v.is_empty() || (v.starts_with("#![") && !v.starts_with("#![doc")))
+        .take_while(|v| v.starts_with("//!"))
+        .map(|v| {
+            let v = &v[3..];
+            v.strip_prefix(' ').unwrap_or(v)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let readme = [
+        "README.md",
+        "README",
+        "readme.md",
+        "Readme.md",
+        "README.markdown",
+    ]
+    .into_iter()
+    .find_map(|v| read_to_string(dir.join(v)).ok());
+    let mut out = vec![];
+    if !docs.trim().is_empty() {
+        out.push(docs);
+    }
+    // crates often paste the README into their crate docs
+    if let Some(readme) =
+        readme.filter(|v| !v.trim().is_empty() && !out.iter().any(|d| d.trim() == v.trim()))
+    {
+        out.push(readme);
+    }
+    out
+}
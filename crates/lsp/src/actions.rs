@@ -1,18 +1,23 @@
 use std::{collections::HashMap, sync::Arc};
 
+use info_provider::features::RemovedFeature;
 use parser::{
     lock::LoggedReadGuard,
     toml::{DepSource, Dependency, DependencyKind, Positioned},
     tree::RangeExclusive,
     Db,
 };
-use rust_version::RustVersion;
+use rust_version::{RustVersion, VersionReq};
+use serde_json::Value;
 use tower_lsp::lsp_types::{
-    CodeAction, CodeActionKind, Position, Range, TextEdit, Url, WorkspaceEdit,
+    CodeAction, CodeActionKind, Command, Diagnostic, Position, Range, TextEdit, Url, WorkspaceEdit,
 };
 
 use crate::lsp::Context;
 
+/// Releases on each side of the locked one offered as "Lock to"
+const NEARBY: usize = 2;
+
 impl Context {
     pub fn upgrade_dep(
         &self,
@@ -58,6 +63,308 @@ impl Context {
             ..Default::default()
         })
     }
+    /// Upgrade that also replaces features the new release dropped with their closest match,
+    /// or removes them when nothing is similar. The title lists the feature changes
+    pub fn upgrade_fix_features(
+        &self,
+        uri: &Url,
+        dep: &Dependency,
+        version: &Positioned<String>,
+        ver: &RustVersion,
+        removed: &[RemovedFeature],
+        lock: &LoggedReadGuard<Db>,
+    ) -> Option<CodeAction> {
+        if removed.is_empty() {
+            return None;
+        }
+        let range = |start: u32, end: u32| {
+            let start = lock.get_offset(uri, start as usize)?;
+            let end = lock.get_offset(uri, end as usize)?;
+            Some(Range::new(
+                Position::new(start.0 as u32, start.1 as u32),
+                Position::new(end.0 as u32, end.1 as u32),
+            ))
+        };
+        let mut edits = vec![TextEdit {
+            range: range(version.start, version.end)?,
+            new_text: format!("\"{ver}\""),
+        }];
+        let features = &dep.features.data;
+        let mut summary = vec![];
+        let mut dropped = vec![false; features.len()];
+        for (index, feature) in features.iter().enumerate() {
+            let Some(removed) = removed.iter().find(|v| v.name == feature.data) else {
+                continue;
+            };
+            match &removed.replacement {
+                Some(new) if !features.iter().any(|v| &v.data == new) => {
+                    summary.push(format!("`{}` → `{new}`", removed.name));
+                    edits.push(TextEdit {
+                        range: range(feature.start, feature.end)?,
+                        new_text: format!("\"{new}\""),
+                    });
+                }
+                _ => {
+                    summary.push(format!("remove `{}`", removed.name));
+                    dropped[index] = true;
+                }
+            }
+        }
+        // dropped features take the separator before them, a leading run the one after it
+        let leading = dropped.iter().take_while(|v| **v).count();
+        if leading > 0 {
+            let end = match features.get(leading) {
+                Some(next) => next.start,
+                None => features[leading - 1].end,
+            };
+            edits.push(TextEdit {
+                range: range(features[0].start, end)?,
+                new_text: String::new(),
+            });
+        }
+        for index in (leading..features.len()).filter(|v| dropped[*v]) {
+            edits.push(TextEdit {
+                range: range(features[index - 1].end, features[index].end)?,
+                new_text: String::new(),
+            });
+        }
+        Some(CodeAction {
+            title: format!("Upgrade to {ver} and fix features: {}", summary.join(", ")),
+            kind: Some(CodeActionKind::QUICKFIX),
+            edit: Some(WorkspaceEdit {
+                changes: Some([(uri.clone(), edits)].into_iter().collect()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// Moves every crate of the dependency's lockstep family to its target version, in all
+    /// manifests of the workspace. Requirement operators like `=` are kept
+    pub async fn family_upgrade(
+        &self,
+        uri: &Url,
+        dep: &Dependency,
+        lock: &LoggedReadGuard<'_, Db>,
+    ) -> Option<CodeAction> {
+        let name = dep.crate_name();
+        let family = lock
+            .families(uri)
+            .await
+            .into_iter()
+            .find(|v| v.contains(&name))?;
+        if family
+            .members
+            .iter()
+            .all(|v| v.conflict.is_none() && v.current == v.target)
+        {
+            return None;
+        }
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for manifest in lock.workspace_manifests(uri) {
+            let Some(toml) = lock.get_toml(manifest) else {
+                continue;
+            };
+            for other in &toml.dependencies {
+                let Some(member) = family.member(&other.data.crate_name()) else {
+                    continue;
+                };
+                let DepSource::Version { value, .. } = &other.data.source else {
+                    continue;
+                };
+                // ranges and wildcards can't be moved to a single release
+                let requirement = value.value.data.trim();
+                if !VersionReq::try_from(requirement).is_ok_and(|v| v.is_simple()) {
+                    continue;
+                }
+                let split = requirement
+                    .find(|v: char| v.is_ascii_digit())
+                    .unwrap_or_default();
+                let (operator, version) = requirement.split_at(split);
+                if version == member.target {
+                    continue;
+                }
+                let (Some(start), Some(end)) = (
+                    lock.get_offset(manifest, value.value.start as usize),
+                    lock.get_offset(manifest, value.value.end as usize),
+                ) else {
+                    continue;
+                };
+                changes.entry(manifest.clone()).or_default().push(TextEdit {
+                    range: Range::new(
+                        Position::new(start.0 as u32, start.1 as u32),
+                        Position::new(end.0 as u32, end.1 as u32),
+                    ),
+                    new_text: format!("\"{operator}{}\"", member.target),
+                });
+            }
+        }
+        if changes.is_empty() {
+            return None;
+        }
+        let targets = family
+            .members
+            .iter()
+            .map(|v| format!("{} {}", v.crate_name, v.target))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Some(CodeAction {
+            title: format!("Upgrade {} family ({targets})", family.name),
+            kind: Some(CodeActionKind::EMPTY),
+            edit: Some(WorkspaceEdit {
+                changes: Some(changes),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// `cargo update` actions for a single dependency. The package is passed as `name@locked`
+    /// so cargo knows which one to update when several versions are locked
+    pub fn lock_actions(
+        &self,
+        uri: &Url,
+        name: &str,
+        requirement: &str,
+        locked: Option<&str>,
+        versions: &[RustVersion],
+    ) -> Vec<CodeAction> {
+        let Some(locked) = locked else {
+            return vec![];
+        };
+        let action = |title: String, precise: Option<&RustVersion>| {
+            let mut arguments = vec![
+                Value::String(uri.to_string()),
+                Value::String(format!("{name}@{locked}")),
+            ];
+            if let Some(precise) = precise {
+                arguments.push(Value::String(precise.to_string()));
+            }
+            CodeAction {
+                title: title.clone(),
+                kind: Some(CodeActionKind::EMPTY),
+                command: Some(Command {
+                    title,
+                    command: "cargo-update".to_owned(),
+                    arguments: Some(arguments),
+                }),
+                ..CodeAction::default()
+            }
+        };
+        let mut res = vec![action(format!("Update {name} (locked {locked})"), None)];
+
+        let (Ok(req), Ok(current)) = (
+            VersionReq::try_from(requirement),
+            RustVersion::try_from(locked),
+        ) else {
+            return res;
+        };
+        let mut allowed = versions
+            .iter()
+            .filter(|v| req.matches(v) && v.to_string() != locked)
+            .collect::<Vec<_>>();
+        allowed.sort();
+        allowed.dedup_by(|a, b| a.to_string() == b.to_string());
+        let newest = allowed.last().copied().filter(|v| *v > &current);
+        if let Some(newest) = newest {
+            res.push(action(
+                format!("Update {name} to newest allowed {newest}"),
+                Some(newest),
+            ));
+        }
+        // the releases right next to the locked one
+        let newer = allowed
+            .iter()
+            .filter(|v| ***v > current && Some(**v) != newest)
+            .take(NEARBY);
+        let older = allowed.iter().rev().filter(|v| ***v < current).take(NEARBY);
+        res.extend(
+            newer
+                .chain(older)
+                .map(|v| action(format!("Lock {name} to {v}"), Some(v))),
+        );
+        res
+    }
+
+    /// Quick fixes for diagnostics that carry the package to `cargo update -p`, with
+    /// `--precise` when they name the release that fixes them
+    pub fn lock_quick_fixes(&self, uri: &Url, diagnostics: &[Diagnostic]) -> Vec<CodeAction> {
+        diagnostics
+            .iter()
+            .filter_map(|diagnostic| {
+                let data = diagnostic.data.as_ref()?;
+                let package = data.get("cargo-update")?.as_str()?;
+                let precise = data.get("precise").and_then(|v| v.as_str());
+                let mut arguments = vec![
+                    Value::String(uri.to_string()),
+                    Value::String(package.to_owned()),
+                ];
+                let title = match precise {
+                    Some(precise) => {
+                        arguments.push(Value::String(precise.to_owned()));
+                        format!("cargo update -p {package} --precise {precise}")
+                    }
+                    None => format!("cargo update -p {package}"),
+                };
+                Some(CodeAction {
+                    title: title.clone(),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    command: Some(Command {
+                        title,
+                        command: "cargo-update".to_owned(),
+                        arguments: Some(arguments),
+                    }),
+                    is_preferred: Some(true),
+                    ..CodeAction::default()
+                })
+            })
+            .collect()
+    }
+
+    /// Quick fixes for advisories whose patched release is outside the requirement, they
+    /// raise the requirement, in the workspace manifest for inherited dependencies
+    pub fn requirement_quick_fixes(
+        &self,
+        uri: &Url,
+        diagnostics: &[Diagnostic],
+        lock: &LoggedReadGuard<Db>,
+    ) -> Vec<CodeAction> {
+        diagnostics
+            .iter()
+            .filter_map(|diagnostic| {
+                let data = diagnostic.data.as_ref()?;
+                if data.get("cargo-update").is_some_and(|v| !v.is_null()) {
+                    return None;
+                }
+                let precise = data.get("precise")?.as_str()?;
+                let (start, end) = (diagnostic.range.start, diagnostic.range.end);
+                let dep = lock.get_dependency(
+                    uri,
+                    (start.line as usize, start.character as usize),
+                    (end.line as usize, end.character as usize),
+                )?;
+                let (target, value) = match &dep.data.source {
+                    DepSource::Version { value, .. } => (uri, &value.value),
+                    DepSource::Workspace(_) => match &lock.inherited(uri, &dep.data)?.source {
+                        DepSource::Version { value, .. } => {
+                            (lock.get_workspace(uri)?, &value.value)
+                        }
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                let ver = RustVersion::try_from(precise).ok();
+                let mut action = self.upgrade_dep(target, value, ver, lock)?;
+                action.title = format!("Upgrade {} to {precise}", dep.data.crate_name());
+                action.kind = Some(CodeActionKind::QUICKFIX);
+                action.diagnostics = Some(vec![diagnostic.clone()]);
+                action.is_preferred = Some(true);
+                Some(action)
+            })
+            .collect()
+    }
+
     fn dep_workspace_actions(
         &self,
         uri: &Url,
@@ -0,0 +1,173 @@
+use std::{process::Stdio, time::Duration};
+
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Command,
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+};
+use tower_lsp::lsp_types::{MessageType, Url};
+
+use crate::lsp::Context;
+
+pub const CARGO_COMMANDS: [&str; 4] = [
+    "cargo-update",
+    "cargo-generate-lockfile",
+    "cargo-fetch",
+    "cargo-tree",
+];
+
+pub enum CargoCommand {
+    /// cargo update [-p name [--precise version]]
+    Update {
+        package: Option<String>,
+        precise: Option<String>,
+    },
+    GenerateLockfile,
+    Fetch,
+    Tree,
+}
+
+impl CargoCommand {
+    /// The first argument is the uri of the document the command was triggered from
+    pub fn parse(command: &str, arguments: &[Value]) -> Option<(Self, Option<Url>)> {
+        let arg = |i: usize| arguments.get(i).and_then(|v| v.as_str());
+        let uri = arg(0).and_then(|v| Url::parse(v).ok());
+        let command = match command {
+            "cargo-update" => CargoCommand::Update {
+                package: arg(1).map(ToOwned::to_owned),
+                precise: arg(2).map(ToOwned::to_owned),
+            },
+            "cargo-generate-lockfile" => CargoCommand::GenerateLockfile,
+            "cargo-fetch" => CargoCommand::Fetch,
+            "cargo-tree" => CargoCommand::Tree,
+            _ => return None,
+        };
+        Some((command, uri))
+    }
+
+    fn args(&self) -> Vec<String> {
+        match self {
+            CargoCommand::Update { package, precise } => {
+                let mut args = vec!["update".to_owned()];
+                if let Some(package) = package {
+                    args.extend(["-p".to_owned(), package.clone()]);
+                    if let Some(precise) = precise {
+                        args.extend(["--precise".to_owned(), precise.clone()]);
+                    }
+                }
+                args
+            }
+            CargoCommand::GenerateLockfile => vec!["generate-lockfile".to_owned()],
+            CargoCommand::Fetch => vec!["fetch".to_owned()],
+            CargoCommand::Tree => vec!["tree".to_owned()],
+        }
+    }
+}
+
+impl Context {
+    /// Runs cargo in the workspace root of the document, streams the output to the client
+    /// and reloads the Cargo.lock afterwards. Returns stdout.
+    pub async fn run_cargo(&self, command: CargoCommand, uri: Option<Url>) -> Option<String> {
+        let (cwd, lock_uri) = {
+            let lock = self.db.read("run_cargo").await;
+            match &uri {
+                Some(uri) => (lock.root_dir(uri), Some(lock.lock_uri(uri))),
+                None => (None, None),
+            }
+        };
+        let args = command.args();
+        let title = format!("cargo {}", args.join(" "));
+        let task = self.info.progress().begin(&title, true);
+
+        let mut cmd = Command::new("cargo");
+        cmd.args(&args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(cwd) = &cwd {
+            cmd.current_dir(cwd);
+        }
+        let mut child = match cmd.spawn() {
+            Ok(v) => v,
+            Err(e) => {
+                self.client
+                    .show_message(MessageType::ERROR, format!("failed to run {title}: {e}"))
+                    .await;
+                return None;
+            }
+        };
+
+        let (tx, mut rx) = unbounded_channel();
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(read_lines(stdout, true, tx.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(read_lines(stderr, false, tx));
+        }
+
+        let mut stdout = vec![];
+        let mut last_err = None;
+        loop {
+            tokio::select! {
+                line = rx.recv() => match line {
+                    Some((is_stdout, line)) => {
+                        self.client
+                            .log_message(MessageType::LOG, format!("[{title}] {line}"))
+                            .await;
+                        match is_stdout {
+                            true => stdout.push(line),
+                            false => {
+                                task.report(line.trim(), None);
+                                last_err = Some(line);
+                            }
+                        }
+                    }
+                    None => break,
+                },
+                _ = tokio::time::sleep(Duration::from_millis(250)) => {
+                    if task.is_cancelled() {
+                        let _ = child.kill().await;
+                    }
+                }
+            }
+        }
+
+        let status = child.wait().await;
+        match status {
+            Ok(status) if status.success() => task.finish("Done"),
+            Ok(_) if task.is_cancelled() => task.finish("Cancelled"),
+            Ok(status) => {
+                let reason = last_err.unwrap_or(status.to_string());
+                self.client
+                    .show_message(MessageType::ERROR, format!("{title} failed: {reason}"))
+                    .await;
+                task.finish("Failed");
+            }
+            Err(e) => {
+                self.client
+                    .show_message(MessageType::ERROR, format!("{title} failed: {e}"))
+                    .await;
+                task.finish("Failed");
+            }
+        }
+
+        if let Some(lock_uri) = lock_uri {
+            self.db.write("run_cargo").await.update_lock(lock_uri).await;
+        }
+        Some(stdout.join("\n"))
+    }
+}
+
+async fn read_lines(
+    reader: impl AsyncRead + Unpin,
+    is_stdout: bool,
+    tx: UnboundedSender<(bool, String)>,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if tx.send((is_stdout, line)).is_err() {
+            break;
+        }
+    }
+}
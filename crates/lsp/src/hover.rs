@@ -1,5 +1,12 @@
 use std::usize;
 
+use info_provider::{
+    api::Root1,
+    changelog::{changelog_excerpt, local_changelog},
+    features::ResolvedFeatures,
+    source::local_docs,
+};
+use parser::features::FeatureExpansion;
 use parser::{
     lock::LoggedReadGuard,
     structs::lock::Source,
@@ -10,7 +17,7 @@ use parser::{
 use rust_version::RustVersion;
 use tower_lsp::lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind, Position, Range, Url};
 
-use crate::lsp::Context;
+use crate::{lockfile::why_section, lsp::Context, utils::to_range};
 
 impl Context {
     async fn hover_version(
@@ -24,21 +31,37 @@ impl Context {
         if range.contains(offset) {
             let start = lock.get_offset(&uri, range.start as usize)?;
             let end = lock.get_offset(&uri, range.end as usize)?;
-            let info = match self
+            let versions = self
                 .info
                 .get_info(dep.data.source.registry(), &dep.data.crate_name())
-                .await
-            {
+                .await;
+            let changes = match &versions {
+                Ok(versions) => {
+                    let mut sections = vec![];
+                    sections.extend(removed_section(lock, uri, &dep.data, versions).await);
+                    sections.extend(changelog_section(lock, uri, &dep.data, versions).await);
+                    Some(sections.join("\n\n---\n\n")).filter(|v| !v.is_empty())
+                }
+                Err(_) => None,
+            };
+            let info = match versions {
                 Ok(v) => format!(
                     "List of all available versions: \n{}",
                     v.into_iter()
-                        .map(|v| format!("- {}", v.vers))
+                        .map(|v| match v.yanked {
+                            true => format!("- ~~{}~~ (yanked)", v.vers),
+                            false => format!("- {}", v.vers),
+                        })
                         .rev()
                         .collect::<Vec<_>>()
                         .join("\n")
                 ),
                 Err(_) => "Couldnt find version info".to_owned(),
             };
+            let info = match changes {
+                Some(changes) => format!("{changes}\n\n---\n\n{info}"),
+                None => info,
+            };
             return Some(Hover {
                 contents: HoverContents::Markup(MarkupContent {
                     kind: MarkupKind::Markdown,
@@ -70,19 +93,40 @@ impl Context {
         if range.contains(offset) {
             let start = lock.get_offset(&uri, range.start as usize)?;
             let end = lock.get_offset(&uri, range.end as usize)?;
-            let lock = lock.get_lock(uri)?.packages();
-            let lock = lock.get(dep.data.name())?.first()?;
-            let mut use_ = false;
-            if let Some(Source::Registry(s)) = &lock.source {
-                use_ = s == "https://github.com/rust-lang/crates.io-index";
+            let package = lock.resolve_dependency(uri, &dep.data);
+            let crate_name = dep.data.crate_name();
+            let mut sections = vec![];
+            if let Some(package) = &package {
+                let version = package.version.to_string();
+                sections.extend(why_section(lock, uri, &crate_name, &version));
             }
-            if !use_ {
+            sections.extend(unification_section(lock, uri, &dep.data).await);
+            let local = match lock.dependency_source_dir(uri, &dep.data).await {
+                Some(dir) => tokio::task::spawn_blocking(move || local_docs(&dir))
+                    .await
+                    .unwrap_or_default(),
+                None => vec![],
+            };
+            match (local.is_empty(), &package) {
+                (false, _) => sections.extend(local),
+                // only crates.io has a readme endpoint
+                (true, Some(package)) => {
+                    let mut use_ = false;
+                    if let Some(Source::Registry(s)) = &package.source {
+                        use_ = s == "https://github.com/rust-lang/crates.io-index"
+                            || s == "sparse+https://index.crates.io/";
+                    }
+                    if use_ {
+                        let version = package.version.to_string();
+                        sections.extend(self.info.get_readme_api(&crate_name, &version).await);
+                    }
+                }
+                (true, None) => {}
+            }
+            if sections.is_empty() {
                 return None;
             }
-            let content = self
-                .info
-                .get_readme_api(&dep.data.crate_name(), &lock.version.to_string())
-                .await?;
+            let content = sections.join("\n\n---\n\n");
 
             return Some(Hover {
                 contents: HoverContents::Markup(MarkupContent {
@@ -132,6 +176,41 @@ impl Context {
             .as_str(),
         )
         .ok()?;
+        let feature = dep.data.features.data.iter().find(|v| v.contains(offset));
+        // path and git dependencies or crates without cached index data get the feature list
+        if let Some(feature) = feature {
+            if let Some(release) = lock.dependency_release(uri, &dep.data).await {
+                let registry = lock
+                    .effective_source(uri, &dep.data)
+                    .and_then(|v| v.registry());
+                let (others, default_features) = lock.requested_features(uri, &dep.data);
+                let expansion = lock
+                    .expand_feature(
+                        uri,
+                        registry,
+                        &release,
+                        &feature.data,
+                        &others,
+                        default_features,
+                    )
+                    .await;
+                let mut value = expansion_section(&feature.data, &expansion);
+                if let Some(docs) = lock
+                    .dependency_feature_docs(uri, &dep.data)
+                    .await
+                    .remove(&feature.data)
+                {
+                    value = format!("{docs}\n\n---\n\n{value}");
+                }
+                return Some(Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value,
+                    }),
+                    range: to_range(lock, uri, RangeExclusive::from(feature).into()),
+                });
+            }
+        }
         if range.contains(offset) {
             let start = lock.get_offset(&uri, range.start as usize)?;
             let end = lock.get_offset(&uri, range.end as usize)?;
@@ -156,6 +235,14 @@ impl Context {
                 ),
                 Err(_) => "Couldnt find feature info".to_owned(),
             };
+            let info = match lock.dependency_features(uri, &dep.data).await {
+                Some(resolved) => format!("{}\n\n---\n\n{info}", effective_section(&resolved)),
+                None => info,
+            };
+            let info = match unification_section(lock, uri, &dep.data).await {
+                Some(section) => format!("{section}\n\n---\n\n{info}"),
+                None => info,
+            };
             return Some(Hover {
                 contents: HoverContents::Markup(MarkupContent {
                     kind: MarkupKind::Markdown,
@@ -176,6 +263,37 @@ impl Context {
         None
     }
 
+    /// Hover on the name of an entry in the manifest's own `[features]`
+    pub async fn hover_own_feature(
+        &self,
+        uri: &Url,
+        position: Position,
+        lock: &LoggedReadGuard<'_, Db>,
+    ) -> Option<Hover> {
+        let offset = lock.get_byte(uri, position.line as usize, position.character as usize)?;
+        let feature = lock
+            .get_toml(uri)?
+            .features
+            .iter()
+            .find(|v| v.data.name.contains(offset))?;
+        let release = lock.manifest_release(uri)?;
+        let name = &feature.data.name;
+        let expansion = lock
+            .expand_feature(uri, None, &release, &name.data, &[], false)
+            .await;
+        let mut value = expansion_section(&name.data, &expansion);
+        if let Some(docs) = &feature.data.docs {
+            value = format!("{docs}\n\n---\n\n{value}");
+        }
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: to_range(lock, uri, RangeExclusive::from(name).into()),
+        })
+    }
+
     pub async fn hover_dep(
         &self,
         uri: &Url,
@@ -202,3 +320,160 @@ impl Context {
         None
     }
 }
+
+/// Features the dependency enables that the newest release no longer has
+async fn removed_section(
+    db: &Db,
+    uri: &Url,
+    dep: &Dependency,
+    versions: &[Root1],
+) -> Option<String> {
+    let msrv = db.msrv(uri);
+    let latest = versions
+        .iter()
+        .rfind(|v| !v.yanked && v.supports_rust(msrv.as_ref()))?;
+    let current = db.dependency_release(uri, dep).await?;
+    let removed =
+        current.removed_features(latest, dep.features.data.iter().map(|v| v.data.as_str()));
+    if removed.is_empty() {
+        return None;
+    }
+    let mut out = format!("**Features removed in {}:**\n", latest.vers);
+    for feature in removed {
+        match feature.replacement {
+            Some(new) => out.push_str(&format!("- `{}`, maybe `{new}`\n", feature.name)),
+            None => out.push_str(&format!("- `{}`\n", feature.name)),
+        }
+    }
+    Some(out)
+}
+
+/// Changelog entries between the locked release and the newest one, read from the newest
+/// release available in the local registry cache
+async fn changelog_section(
+    db: &Db,
+    uri: &Url,
+    dep: &Dependency,
+    versions: &[Root1],
+) -> Option<String> {
+    let package = db
+        .resolve_dependency(uri, dep)
+        .filter(|v| v.is_registry())?;
+    let locked = RustVersion::try_from(package.version().to_string().as_str()).ok()?;
+    let locked = &locked;
+    let latest = versions
+        .iter()
+        .rev()
+        .filter(|v| !v.yanked)
+        .filter_map(|v| v.ver())
+        .find(|v| locked.is_pre_release() || !v.is_pre_release())?;
+    if &latest <= locked {
+        return None;
+    }
+    let name = dep.crate_name();
+    let (source, content) = tokio::task::spawn_blocking(move || local_changelog(&name))
+        .await
+        .ok()??;
+    let to = match source < latest {
+        true => &source,
+        false => &latest,
+    };
+    let excerpt = changelog_excerpt(&content, locked, to)?;
+    let mut out = format!("**Changes since {locked}**");
+    if to < &latest {
+        out.push_str(&format!(
+            " (up to {to}, the newest release in the local cache)"
+        ));
+    }
+    Some(format!("{out}\n\n{excerpt}"))
+}
+
+/// Features that end up enabled and the optional crates they activate
+fn effective_section(resolved: &ResolvedFeatures) -> String {
+    let list = |items: &std::collections::BTreeSet<String>| {
+        items
+            .iter()
+            .map(|v| format!("`{v}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut out = format!("**Effective features:** {}", list(&resolved.features));
+    if !resolved.optional_deps.is_empty() {
+        out.push_str(&format!(
+            "\n\n**Optional crates pulled in:** {}",
+            list(&resolved.optional_deps)
+        ));
+    }
+    out
+}
+
+/// Features other workspace members turn on for the same crate, cargo unifies them
+async fn unification_section(db: &Db, uri: &Url, dep: &Dependency) -> Option<String> {
+    let elsewhere = db.features_elsewhere(uri, dep).await;
+    if elsewhere.is_empty() {
+        return None;
+    }
+    let mut out = "**Features enabled elsewhere in the workspace:**\n".to_owned();
+    for (feature, members) in elsewhere {
+        let members = members.into_iter().collect::<Vec<_>>().join(", ");
+        out.push_str(&format!("- `{feature}` by {members}\n"));
+    }
+    Some(out)
+}
+
+/// Transitive expansion of a single feature
+fn expansion_section(feature: &str, expansion: &FeatureExpansion) -> String {
+    let code = |items: &mut dyn Iterator<Item = &String>| {
+        items
+            .map(|v| format!("`{v}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let resolved = &expansion.resolved;
+    let mut out = format!("**`{feature}` enables**\n");
+    let sub = resolved
+        .features
+        .iter()
+        .filter(|v| *v != feature && !resolved.optional_deps.contains(*v))
+        .collect::<Vec<_>>();
+    if !sub.is_empty() {
+        out.push_str(&format!("- features: {}\n", code(&mut sub.into_iter())));
+    }
+    for dep in &resolved.optional_deps {
+        let features = expansion
+            .tree
+            .crates
+            .get(dep)
+            .map(|v| &v.1)
+            .filter(|v| !v.is_empty());
+        match features {
+            Some(features) => out.push_str(&format!(
+                "- optional crate `{dep}` with {}\n",
+                code(&mut features.iter())
+            )),
+            None => out.push_str(&format!("- optional crate `{dep}`\n")),
+        }
+    }
+    for (dep, features) in &resolved.dep_features {
+        if !resolved.optional_deps.contains(dep) {
+            out.push_str(&format!(
+                "- `{dep}` features: {}\n",
+                code(&mut features.iter())
+            ));
+        }
+    }
+    if !expansion.added.is_empty() {
+        out.push_str(&format!(
+            "\nAdds {} packages to the build, {} of them not in Cargo.lock yet\n",
+            expansion.added.len(),
+            expansion.not_locked.len()
+        ));
+    }
+    if !expansion.tree.missing.is_empty() {
+        out.push_str(&format!(
+            "\nIndex data for {} isn't cached yet, the package count is incomplete\n",
+            code(&mut expansion.tree.missing.iter())
+        ));
+    }
+    out
+}
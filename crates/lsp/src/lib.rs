@@ -1,5 +1,9 @@
 pub mod actions;
+mod cargo;
 mod hover;
+mod lockfile;
 mod lsp;
+mod progress;
 mod utils;
+mod watch;
 pub use lsp::main;
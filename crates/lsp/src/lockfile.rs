@@ -0,0 +1,236 @@
+use info_provider::resolver::UpgradeImpact;
+use parser::{
+    dependency_tree::TreeNode,
+    structs::lock::{Duplicate, LockPackage, WhyNode},
+    Db,
+};
+use serde::Deserialize;
+use tower_lsp::{
+    jsonrpc::Result,
+    lsp_types::{
+        DocumentLink, DocumentSymbol, Hover, HoverContents, MarkupContent, MarkupKind, Position,
+        SymbolKind, TextDocumentIdentifier, Url,
+    },
+};
+
+use crate::{lsp::Context, utils::to_range};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WhyParams {
+    pub text_document: TextDocumentIdentifier,
+    pub name: String,
+    /// All locked versions if missing
+    pub version: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpgradePreviewParams {
+    pub text_document: TextDocumentIdentifier,
+    /// Crate name of the dependency
+    pub name: String,
+    /// The newest release that fits the package's rust-version if missing
+    pub requirement: Option<String>,
+}
+
+impl Context {
+    /// `cargotom/duplicates`: packages locked at more than one version in the lock file
+    /// of the document's workspace
+    pub async fn duplicates(&self, params: TextDocumentIdentifier) -> Result<Vec<Duplicate>> {
+        let lock = self.db.read("duplicates").await;
+        Ok(lock.duplicates(&params.uri).unwrap_or_default())
+    }
+
+    /// `cargotom/dependencyTree`: resolved dependency graph of the manifest
+    pub async fn dependency_tree(
+        &self,
+        params: TextDocumentIdentifier,
+    ) -> Result<Option<TreeNode>> {
+        let lock = self.db.read("dependency_tree").await;
+        Ok(lock.dependency_tree(&params.uri))
+    }
+
+    /// `cargotom/upgradePreview`: packages an upgrade would add, remove and change in the
+    /// lock file, with the conflicts and rust-version problems it runs into
+    pub async fn upgrade_preview(
+        &self,
+        params: UpgradePreviewParams,
+    ) -> Result<Option<UpgradeImpact>> {
+        let uri = params.text_document.uri;
+        let requirement = match params.requirement {
+            Some(requirement) => requirement,
+            None => {
+                let (registry, msrv) = {
+                    let lock = self.db.read("upgrade_preview").await;
+                    let Some(dep) = lock.get_toml(&uri).and_then(|v| {
+                        v.dependencies
+                            .iter()
+                            .find(|v| v.data.crate_name() == params.name)
+                    }) else {
+                        return Ok(None);
+                    };
+                    let registry = lock
+                        .effective_source(&uri, &dep.data)
+                        .and_then(|v| v.registry())
+                        .map(ToOwned::to_owned);
+                    (registry, lock.msrv(&uri))
+                };
+                let latest = self
+                    .info
+                    .get_info(registry.as_deref(), &params.name)
+                    .await
+                    .ok()
+                    .and_then(|v| {
+                        v.into_iter()
+                            .rfind(|v| !v.yanked && v.supports_rust(msrv.as_ref()))
+                    });
+                match latest {
+                    Some(latest) => latest.vers,
+                    None => return Ok(None),
+                }
+            }
+        };
+        let preview =
+            self.db
+                .read("upgrade_preview")
+                .await
+                .upgrade_preview(&uri, &params.name, &requirement);
+        Ok(match preview {
+            Some(preview) => Some(preview.await),
+            None => None,
+        })
+    }
+
+    /// `cargotom/why`: inverted dependency trees from a package back to the workspace members
+    pub async fn why(&self, params: WhyParams) -> Result<Vec<WhyNode>> {
+        let lock = self.db.read("why").await;
+        Ok(lock
+            .why(
+                &params.text_document.uri,
+                &params.name,
+                params.version.as_deref(),
+            )
+            .unwrap_or_default())
+    }
+}
+
+/// Markdown list of the paths that pull a package in
+pub fn why_section(db: &Db, uri: &Url, name: &str, version: &str) -> Option<String> {
+    const LIMIT: usize = 5;
+    let trees = db.why(uri, name, Some(version))?;
+    let paths = trees
+        .iter()
+        .flat_map(|v| v.paths(LIMIT + 1))
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return None;
+    }
+    let mut out = format!("**Why is `{name} {version}` here?**\n");
+    for path in paths.iter().take(LIMIT) {
+        out.push_str(&format!("- {}\n", path.join(" → ")));
+    }
+    if paths.len() > LIMIT {
+        out.push_str("- …\n");
+    }
+    Some(out)
+}
+
+/// Hover on a `[[package]]` entry of a Cargo.lock: who depends on it and how the
+/// workspace members reach it
+pub fn lock_hover(db: &Db, uri: &Url, position: Position) -> Option<Hover> {
+    let index = db.lock_package_at(uri, (position.line as usize, position.character as usize))?;
+    let lock = db.lock_file(uri)?;
+    let package = lock.entry(index)?;
+    let mut value = format!("**{} {}**\n", package.name, package.version);
+    if let Some(source) = &package.source {
+        value.push_str(&format!("\n`{source}`\n"));
+    }
+    let dependents = lock
+        .graph()
+        .dependents(index)
+        .iter()
+        .filter_map(|v| lock.entry(*v))
+        .map(|v| format!("`{} {}`", v.name, v.version))
+        .collect::<Vec<_>>();
+    if !dependents.is_empty() {
+        value.push_str(&format!("\nRequired by {}\n", dependents.join(", ")));
+    }
+    if let Some(why) = why_section(db, uri, &package.name, &package.version) {
+        value.push('\n');
+        value.push_str(&why);
+    }
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: to_range(db, uri, package.name_span.clone()),
+    })
+}
+
+/// One symbol per `[[package]]` and `[[patch.unused]]` entry of a Cargo.lock
+pub fn lock_symbols(db: &Db, uri: &Url) -> Option<Vec<DocumentSymbol>> {
+    let len = db.lock_file(uri)?.entries().len();
+    let symbols = db
+        .lock_entry_spans(uri)?
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, (package, span))| {
+            let detail = match i < len {
+                true => package.source.clone(),
+                false => Some("unused patch".to_owned()),
+            };
+            #[allow(deprecated)]
+            Some(DocumentSymbol {
+                name: format!("{} {}", package.name, package.version),
+                detail,
+                kind: SymbolKind::PACKAGE,
+                tags: None,
+                deprecated: None,
+                range: to_range(db, uri, span)?,
+                selection_range: to_range(db, uri, package.name_span.clone())?,
+                children: None,
+            })
+        })
+        .collect();
+    Some(symbols)
+}
+
+/// Links the `source` of each locked package to its crates.io page, git repository or
+/// registry
+pub fn lock_links(db: &Db, uri: &Url) -> Option<Vec<DocumentLink>> {
+    let lock = db.lock_file(uri)?;
+    let links = lock
+        .entries()
+        .iter()
+        .filter_map(|package| {
+            let span = package.source_span.as_ref()?;
+            // the span includes the quotes
+            let range = to_range(db, uri, span.start + 1..span.end - 1)?;
+            Some(DocumentLink {
+                range,
+                target: Some(source_link(package)?),
+                tooltip: None,
+                data: None,
+            })
+        })
+        .collect();
+    Some(links)
+}
+
+fn source_link(package: &LockPackage) -> Option<Url> {
+    let source = package.source.as_deref()?;
+    let url = match source {
+        "registry+https://github.com/rust-lang/crates.io-index"
+        | "sparse+https://index.crates.io/" => format!(
+            "https://crates.io/crates/{}/{}",
+            package.name, package.version
+        ),
+        _ => {
+            let (_, url) = source.split_once('+')?;
+            url.split(['?', '#']).next()?.to_owned()
+        }
+    };
+    Url::parse(&url).ok()
+}
@@ -2,6 +2,8 @@ use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use info_provider::progress::ProgressEvent;
+use info_provider::source::registry_source;
 use info_provider::InfoProvider;
 use parser::config::Config;
 use parser::lock::LoggedRwLock;
@@ -9,17 +11,22 @@ use parser::toml::{DepSource, OptionalKey, Positioned};
 use parser::tree::RangeExclusive;
 use parser::{Db, Indent};
 use rust_version::RustVersion;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::{Mutex, RwLock};
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::{
-    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    ClientCapabilities, CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
     CodeActionProviderCapability, CodeActionResponse, Command, CompletionItem, CompletionItemKind,
-    CompletionOptions, CompletionParams, CompletionResponse, CompletionTextEdit,
-    DidChangeTextDocumentParams, DidChangeWorkspaceFoldersParams, DidOpenTextDocumentParams,
-    DocumentFormattingParams, ExecuteCommandParams, Hover, HoverContents, HoverParams,
-    HoverProviderCapability, InlayHint, InlayHintKind, InlayHintParams, MarkupKind, MessageType,
-    OneOf, Position, Range, ServerCapabilities, ServerInfo, SignatureHelpOptions,
-    TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions, TextEdit, Url,
-    WorkspaceEdit, WorkspaceFoldersServerCapabilities, WorkspaceServerCapabilities,
+    CompletionItemTag, CompletionOptions, CompletionParams, CompletionResponse, CompletionTextEdit,
+    DidChangeTextDocumentParams, DidChangeWatchedFilesParams, DidChangeWorkspaceFoldersParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentFormattingParams, DocumentLink,
+    DocumentLinkOptions, DocumentLinkParams, DocumentSymbolParams, DocumentSymbolResponse,
+    Documentation, ExecuteCommandParams, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializedParams, InlayHint, InlayHintKind, InlayHintParams,
+    MarkupContent, MarkupKind, MessageType, OneOf, Position, Range, ServerCapabilities, ServerInfo,
+    SignatureHelpOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextEdit, Url, WorkspaceEdit, WorkspaceFoldersServerCapabilities,
+    WorkspaceServerCapabilities,
 };
 use tower_lsp::{
     async_trait,
@@ -27,10 +34,15 @@ use tower_lsp::{
     Client, LanguageServer, LspService, Server,
 };
 
+use crate::cargo::{CargoCommand, CARGO_COMMANDS};
+use crate::lockfile::{lock_hover, lock_links, lock_symbols};
+
 pub struct Context {
     pub client: Client,
-    db: Arc<LoggedRwLock<Db>>,
+    pub(crate) db: Arc<LoggedRwLock<Db>>,
     pub info: Arc<InfoProvider>,
+    pub(crate) capabilities: RwLock<ClientCapabilities>,
+    pub(crate) progress_events: Mutex<Option<UnboundedReceiver<ProgressEvent>>>,
 }
 
 macro_rules! try_option {
@@ -61,7 +73,7 @@ impl LanguageServer for Context {
     async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
         let mut lock = self.db.write("did_change_workspace_folders").await;
         for remove in params.event.removed {
-            lock.remove_workspace(&remove.uri);
+            lock.remove_workspace(&remove.uri).await;
         }
         for add in params.event.added {
             let mut root = add.uri;
@@ -70,10 +82,13 @@ impl LanguageServer for Context {
             }
             lock.try_init(&root.join("Cargo.toml").unwrap()).await;
         }
+        drop(lock);
+        self.prefetch().await;
     }
     async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
         let c = self.db.clone();
         let config = load_config(&params);
+        *self.capabilities.write().await = params.capabilities.clone();
         let mut lock = self.db.write("initialize").await;
         lock.config = config;
         self.info.set_per_page(lock.config.per_page).await;
@@ -94,6 +109,9 @@ impl LanguageServer for Context {
             }
             lock.try_init(&root.join("Cargo.toml").unwrap()).await;
         }
+        drop(lock);
+        self.prefetch().await;
+        self.load_advisories().await;
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Options(
@@ -124,7 +142,7 @@ impl LanguageServer for Context {
                 implementation_provider: None,
                 references_provider: None,
                 document_highlight_provider: None,
-                document_symbol_provider: None,
+                document_symbol_provider: Some(OneOf::Left(true)),
                 workspace_symbol_provider: None,
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
                 code_lens_provider: None,
@@ -132,16 +150,19 @@ impl LanguageServer for Context {
                 document_range_formatting_provider: None,
                 document_on_type_formatting_provider: None,
                 rename_provider: None,
-                document_link_provider: None,
+                document_link_provider: Some(DocumentLinkOptions {
+                    resolve_provider: None,
+                    work_done_progress_options: Default::default(),
+                }),
                 color_provider: None,
                 folding_range_provider: None,
                 declaration_provider: None,
                 execute_command_provider: Some(tower_lsp::lsp_types::ExecuteCommandOptions {
-                    commands: vec![
-                        "open_url".to_string(),
-                        "cargo-update".to_string(),
-                        "open-src".to_string(),
-                    ],
+                    commands: ["open_url", "open-src"]
+                        .into_iter()
+                        .chain(CARGO_COMMANDS)
+                        .map(ToOwned::to_owned)
+                        .collect(),
                     ..Default::default()
                 }),
                 workspace: Some(WorkspaceServerCapabilities {
@@ -167,6 +188,15 @@ impl LanguageServer for Context {
         })
     }
 
+    async fn initialized(&self, _: InitializedParams) {
+        self.start_progress().await;
+        self.register_file_watchers().await;
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        self.watched_files_changed(params.changes).await;
+    }
+
     async fn shutdown(&self) -> Result<()> {
         Ok(())
     }
@@ -175,7 +205,15 @@ impl LanguageServer for Context {
         let uri = params.text_document.uri.clone();
         if uri.to_string().ends_with("/Cargo.lock") {
             let mut lock = self.db.write("change").await;
-
+            for change in params.content_changes {
+                let range = change.range.map(|v| {
+                    (
+                        (v.start.line as usize, v.start.character as usize),
+                        (v.end.line as usize, v.end.character as usize),
+                    )
+                });
+                lock.update(&uri, range, &change.text);
+            }
             lock.update_lock(uri).await;
             return;
         }
@@ -203,6 +241,8 @@ impl LanguageServer for Context {
         let uri = params.text_document.uri;
         if uri.to_string().ends_with("/Cargo.lock") {
             let mut lock = self.db.write("open").await;
+            lock.set_open(&uri);
+            lock.update(&uri, None, &params.text_document.text);
             lock.update_lock(uri).await;
             return;
         }
@@ -211,17 +251,28 @@ impl LanguageServer for Context {
         }
         {
             let mut lock = self.db.write("open").await;
+            lock.set_open(&uri);
             lock.update(&uri, None, &params.text_document.text);
             lock.reload(uri).await;
         }
         let _ = self.client.inlay_hint_refresh().await;
     }
 
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        if !uri.to_string().ends_with("/Cargo.lock") && !self.shoud_allow_user(&uri) {
+            return;
+        }
+        let mut lock = self.db.write("close").await;
+        lock.close(&uri).await;
+    }
+
     async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
         if !self.shoud_allow_user(&params.text_document.uri) {
             return Ok(None);
         }
         let mut actions = vec![];
+        let uri = params.text_document.uri;
 
         let mut actions_last = vec![CodeActionOrCommand::CodeAction(CodeAction {
             title: "Update All".to_string(),
@@ -229,17 +280,25 @@ impl LanguageServer for Context {
             command: Some(Command {
                 title: "Update All".to_string(),
                 command: "cargo-update".to_string(),
-                arguments: None,
+                arguments: Some(vec![serde_json::Value::String(uri.to_string())]),
             }),
             ..CodeAction::default()
         })];
 
-        let uri = params.text_document.uri;
-
         if params.range.start.line == 0 || params.range.end.line == 0 {
             actions.extend(self.first_line_actions().await);
         }
+        actions.extend(
+            self.lock_quick_fixes(&uri, &params.context.diagnostics)
+                .into_iter()
+                .map(CodeActionOrCommand::CodeAction),
+        );
         let lock = self.db.read("code_action").await;
+        actions.extend(
+            self.requirement_quick_fixes(&uri, &params.context.diagnostics, &lock)
+                .into_iter()
+                .map(CodeActionOrCommand::CodeAction),
+        );
         if let Some(dep) = lock.get_dependency(
             &uri,
             (
@@ -259,21 +318,63 @@ impl LanguageServer for Context {
                         &dep.data.crate_name(),
                     )
                     .await;
-                match version_info {
+                let crate_name = &dep.data.crate_name();
+                let locked = lock
+                    .resolve_dependency(&uri, &dep.data)
+                    .map(|v| v.version.to_string());
+                let msrv = lock.msrv(&uri);
+                match &version_info {
                     Ok(data) => {
-                        if let Some(last) = data.last() {
+                        if let Some(last) = data
+                            .iter()
+                            .rfind(|v| !v.yanked && v.supports_rust(msrv.as_ref()))
+                        {
                             if let Some(upgrade_dep) =
                                 self.upgrade_dep(&uri, &value.value, last.ver(), &lock)
                             {
                                 actions.push(CodeActionOrCommand::CodeAction(upgrade_dep));
+                                let removed = match lock.dependency_release(&uri, &dep.data).await {
+                                    Some(current) => current.removed_features(
+                                        last,
+                                        dep.data.features.data.iter().map(|v| v.data.as_str()),
+                                    ),
+                                    None => vec![],
+                                };
+                                if let Some(fix) = last.ver().and_then(|ver| {
+                                    self.upgrade_fix_features(
+                                        &uri,
+                                        &dep.data,
+                                        &value.value,
+                                        &ver,
+                                        &removed,
+                                        &lock,
+                                    )
+                                }) {
+                                    actions.push(CodeActionOrCommand::CodeAction(fix));
+                                }
                             }
                         }
                     }
                     _ => {}
                 }
-                let version = &value.value.data;
-
-                let crate_name = &dep.data.crate_name();
+                let versions = version_info
+                    .iter()
+                    .flatten()
+                    .filter(|v| !v.yanked && v.supports_rust(msrv.as_ref()))
+                    .filter_map(|v| v.ver())
+                    .collect::<Vec<_>>();
+                actions_last.extend(
+                    self.lock_actions(
+                        &uri,
+                        crate_name,
+                        &value.value.data,
+                        locked.as_deref(),
+                        &versions,
+                    )
+                    .into_iter()
+                    .map(CodeActionOrCommand::CodeAction),
+                );
+                let version = &locked.unwrap_or(value.value.data.clone());
 
                 let open_page =
                     |actions_last: &mut Vec<CodeActionOrCommand>, name, url: &String| {
@@ -333,6 +434,7 @@ impl LanguageServer for Context {
                 let workspace_uri = lock.get_workspace(&uri);
                 let workspace = workspace_uri.as_ref().and_then(|v| lock.get_toml(v));
                 if let (Some(workspace_uri), Some(workspace)) = (workspace_uri, &workspace) {
+                    let msrv = lock.msrv(workspace_uri);
                     if workspace
                         .dependencies
                         .iter()
@@ -344,9 +446,15 @@ impl LanguageServer for Context {
                             .get_info(None, &dep.data.crate_name())
                             .await
                             .map(|v| {
-                                v.into_iter().rfind(|v| match lock.config.stable_version {
-                                    false => true,
-                                    true => v.ver().map(|v| v.is_pre_release()) == Some(false),
+                                v.into_iter().rfind(|v| {
+                                    !v.yanked
+                                        && v.supports_rust(msrv.as_ref())
+                                        && match lock.config.stable_version {
+                                            false => true,
+                                            true => {
+                                                v.ver().map(|v| v.is_pre_release()) == Some(false)
+                                            }
+                                        }
                                 })
                             })
                         {
@@ -393,6 +501,9 @@ impl LanguageServer for Context {
                     }
                 }
             }
+            if let Some(action) = self.family_upgrade(&uri, &dep.data, &lock).await {
+                actions.push(CodeActionOrCommand::CodeAction(action));
+            }
             if let Some(a) = self.dep_actions(&uri, dep, &lock) {
                 actions.extend(a.into_iter().map(CodeActionOrCommand::CodeAction));
             }
@@ -406,19 +517,31 @@ impl LanguageServer for Context {
         &self,
         mut params: ExecuteCommandParams,
     ) -> tower_lsp::jsonrpc::Result<Option<serde_json::Value>> {
-        if params.command == "cargo-update" {
-            let _ = std::process::Command::new("cargo").arg("update").spawn();
-            return Ok(None);
+        if let Some((command, uri)) = CargoCommand::parse(&params.command, &params.arguments) {
+            let output = self.run_cargo(command, uri).await;
+            return Ok(output.map(serde_json::Value::String));
         }
 
         if params.command == "open-src" {
             let name = params.arguments.get(0).and_then(|arg| arg.as_str());
             let version = params.arguments.get(1).and_then(|arg| arg.as_str());
-            let mut src = if let Some(name) = name {
-                self.info.get_crate_repository(name).await
-            } else {
-                None
+            let mut src = match (name, version) {
+                (Some(name), Some(version)) => registry_source(name, version).map(|v| {
+                    let lib = v.join("src").join("lib.rs");
+                    match lib.is_file() {
+                        true => lib,
+                        false => v.join("Cargo.toml"),
+                    }
+                    .to_string_lossy()
+                    .to_string()
+                }),
+                _ => None,
             };
+            if src.is_none() {
+                if let Some(name) = name {
+                    src = self.info.get_crate_repository(name).await;
+                }
+            }
             if src.is_none() {
                 match (name, version) {
                     (Some(name), Some(version)) => {
@@ -439,11 +562,11 @@ impl LanguageServer for Context {
         if params.command == "open_url" {
             let mut args = params.arguments.iter();
             if let Some(url) = args.next().and_then(|arg| arg.as_str()) {
-                if let Err(e) = webbrowser::open(url) {
+                if let Err(e) = self.open_url(url).await {
                     self.client
                         .show_message(
                             MessageType::WARNING,
-                            format!("failed to open browser {}", e.to_string()),
+                            format!("failed to open browser {}", e),
                         )
                         .await;
                     return Err(tower_lsp::jsonrpc::Error::invalid_params(
@@ -490,18 +613,24 @@ impl LanguageServer for Context {
             return Ok(None);
         }
 
-        let v = self
-            .db
-            .read("inlay_hint")
-            .await
-            .hints(&params.text_document.uri)
+        let lock = self.db.read("inlay_hint").await;
+        let uri = &params.text_document.uri;
+        let features = lock.feature_hints(uri).await.unwrap_or_default();
+        let v = lock
+            .hints(uri)
             .await
             .unwrap_or_default()
             .into_iter()
+            .map(|v| (v.0, v.1.label(), InlayHintKind::TYPE))
+            .chain(
+                features
+                    .into_iter()
+                    .map(|v| (v.0, v.1, InlayHintKind::PARAMETER)),
+            )
             .map(|v| InlayHint {
                 position: Position::new(v.0 .0 as u32, v.0 .1 as u32),
-                label: tower_lsp::lsp_types::InlayHintLabel::String(v.1.label()),
-                kind: Some(InlayHintKind::TYPE),
+                label: tower_lsp::lsp_types::InlayHintLabel::String(v.1),
+                kind: Some(v.2),
                 text_edits: None,
                 tooltip: None,
                 padding_left: Some(true),
@@ -514,6 +643,14 @@ impl LanguageServer for Context {
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         let uri = params.text_document_position_params.text_document.uri;
+        if uri.to_string().ends_with("/Cargo.lock") {
+            let lock = self.db.read("hover").await;
+            return Ok(lock_hover(
+                &lock,
+                &uri,
+                params.text_document_position_params.position,
+            ));
+        }
         if !self.shoud_allow_user(&uri) {
             return Ok(None);
         }
@@ -525,6 +662,12 @@ impl LanguageServer for Context {
         {
             return Ok(Some(h));
         }
+        if let Some(h) = self
+            .hover_own_feature(&uri, params.text_document_position_params.position, &lock)
+            .await
+        {
+            return Ok(Some(h));
+        }
 
         let path = lock
             .get_path(
@@ -563,6 +706,19 @@ impl LanguageServer for Context {
         Ok(None)
     }
 
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let lock = self.db.read("document_symbol").await;
+        Ok(lock_symbols(&lock, &params.text_document.uri).map(DocumentSymbolResponse::Nested))
+    }
+
+    async fn document_link(&self, params: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
+        let lock = self.db.read("document_link").await;
+        Ok(lock_links(&lock, &params.text_document.uri))
+    }
+
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         let uri = params.text_document_position.text_document.uri;
         if !self.shoud_allow_user(&uri) {
@@ -671,15 +827,33 @@ impl LanguageServer for Context {
                         )
                         .await
                         .unwrap_or_default();
+                    // releases needing a newer Rust than the package declares come after the
+                    // compatible ones, yanked versions are crossed out and listed last
+                    let msrv = lock.msrv(&uri);
+                    let (available, yanked): (Vec<_>, Vec<_>) =
+                        info.into_iter().rev().partition(|v| !v.yanked);
+                    let (compatible, newer_rust): (Vec<_>, Vec<_>) = available
+                        .into_iter()
+                        .partition(|v| v.supports_rust(msrv.as_ref()));
                     return Ok(Some(CompletionResponse::Array(
-                        info.into_iter()
-                            .rev()
+                        compatible
+                            .into_iter()
+                            .chain(newer_rust)
+                            .chain(yanked)
                             .filter(|v| v.vers.starts_with(slice))
                             .enumerate()
                             .map(|(i, v)| CompletionItem {
                                 label: v.vers.clone(),
                                 kind: Some(CompletionItemKind::MODULE),
-                                detail: None,
+                                detail: match v.yanked {
+                                    true => Some("yanked".to_owned()),
+                                    false => v
+                                        .rust_version
+                                        .as_ref()
+                                        .filter(|_| !v.supports_rust(msrv.as_ref()))
+                                        .map(|r| format!("needs Rust {r}")),
+                                },
+                                tags: v.yanked.then(|| vec![CompletionItemTag::DEPRECATED]),
                                 sort_text: Some(format!("{:06}", i)),
                                 text_edit: Some(CompletionTextEdit::Edit(TextEdit {
                                     range: Range::new(
@@ -734,6 +908,7 @@ impl LanguageServer for Context {
                         .rfind(|v| v.ver() == version)
                         .map(|v| v.feature_all())
                         .unwrap_or_default();
+                    let mut docs = lock.dependency_feature_docs(&uri, &dep.data).await;
 
                     let start = try_option!(lock.get_offset(&uri, feat.start as usize));
                     let end = try_option!(lock.get_offset(&uri, feat.end as usize));
@@ -753,6 +928,12 @@ impl LanguageServer for Context {
                                 label: v.clone(),
                                 kind: Some(CompletionItemKind::MODULE),
                                 detail: None,
+                                documentation: docs.remove(&v).map(|docs| {
+                                    Documentation::MarkupContent(MarkupContent {
+                                        kind: MarkupKind::Markdown,
+                                        value: docs,
+                                    })
+                                }),
                                 text_edit: Some(CompletionTextEdit::Edit(TextEdit {
                                     range: Range::new(
                                         Position {
@@ -818,11 +999,22 @@ pub async fn main(path: PathBuf) {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
     let info = Arc::new(InfoProvider::new(50, false, path).await);
+    let progress_events = info.progress().subscribe();
     let (client, server) = LspService::build(|client| Context {
         client: client.clone(),
         db: Db::new(client, info.clone()),
         info,
+        capabilities: Default::default(),
+        progress_events: Mutex::new(Some(progress_events)),
     })
+    .custom_method(
+        "window/workDoneProgress/cancel",
+        Context::work_done_progress_cancel,
+    )
+    .custom_method("cargotom/duplicates", Context::duplicates)
+    .custom_method("cargotom/why", Context::why)
+    .custom_method("cargotom/dependencyTree", Context::dependency_tree)
+    .custom_method("cargotom/upgradePreview", Context::upgrade_preview)
     .finish();
 
     Server::new(stdin, stdout, server).serve(client).await;
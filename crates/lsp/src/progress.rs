@@ -0,0 +1,150 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use info_provider::progress::ProgressEvent;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tower_lsp::{
+    lsp_types::{
+        notification::Progress, request::WorkDoneProgressCreate, MessageType, NumberOrString,
+        ProgressParams, ProgressParamsValue, WorkDoneProgress, WorkDoneProgressBegin,
+        WorkDoneProgressCancelParams, WorkDoneProgressCreateParams, WorkDoneProgressEnd,
+        WorkDoneProgressReport,
+    },
+    Client,
+};
+
+use crate::lsp::Context;
+
+impl Context {
+    /// Starts forwarding progress of the info provider to the client
+    pub async fn start_progress(&self) {
+        let Some(events) = self.progress_events.lock().await.take() else {
+            return;
+        };
+        let supported = self
+            .capabilities
+            .read()
+            .await
+            .window
+            .as_ref()
+            .and_then(|v| v.work_done_progress)
+            .unwrap_or_default();
+        tokio::spawn(forward_progress(self.client.clone(), supported, events));
+    }
+
+    pub async fn work_done_progress_cancel(&self, params: WorkDoneProgressCancelParams) {
+        if let NumberOrString::String(token) = params.token {
+            self.info.progress().cancel(&token);
+        }
+    }
+
+    /// Fetches the index data of all registry dependencies in the background
+    pub async fn prefetch(&self) {
+        let crates = self.db.read("prefetch").await.registry_dependencies();
+        let info = self.info.clone();
+        let db = self.db.clone();
+        tokio::spawn(async move {
+            info.prefetch(crates).await;
+            db.read("prefetch done").await.analyze(None).await;
+        });
+    }
+
+    /// Loads the RustSec advisories in the background and reruns the diagnostics once they
+    /// are there
+    pub async fn load_advisories(&self) {
+        let lock = self.db.read("load_advisories").await;
+        if !lock.config.advisory_warnings {
+            return;
+        }
+        let path = lock.config.advisory_db.clone().map(PathBuf::from);
+        drop(lock);
+        let info = self.info.clone();
+        let db = self.db.clone();
+        tokio::spawn(async move {
+            if info.load_advisories(path).await > 0 {
+                db.read("advisories loaded").await.analyze(None).await;
+            }
+        });
+    }
+}
+
+/// Clients without work done progress support only get log messages
+async fn forward_progress(
+    client: Client,
+    supported: bool,
+    mut events: UnboundedReceiver<ProgressEvent>,
+) {
+    let mut active = HashSet::new();
+    while let Some(event) = events.recv().await {
+        match event {
+            ProgressEvent::Begin {
+                token,
+                title,
+                cancellable,
+            } => {
+                if !supported {
+                    client.log_message(MessageType::INFO, title).await;
+                    continue;
+                }
+                let created = client
+                    .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                        token: NumberOrString::String(token.clone()),
+                    })
+                    .await;
+                if created.is_err() {
+                    continue;
+                }
+                active.insert(token.clone());
+                send(
+                    &client,
+                    token,
+                    WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                        title,
+                        cancellable: Some(cancellable),
+                        message: None,
+                        percentage: None,
+                    }),
+                )
+                .await;
+            }
+            ProgressEvent::Report {
+                token,
+                message,
+                percentage,
+            } => {
+                if active.contains(&token) {
+                    send(
+                        &client,
+                        token,
+                        WorkDoneProgress::Report(WorkDoneProgressReport {
+                            cancellable: None,
+                            message,
+                            percentage,
+                        }),
+                    )
+                    .await;
+                }
+            }
+            ProgressEvent::End { token, message } => {
+                if active.remove(&token) {
+                    send(
+                        &client,
+                        token,
+                        WorkDoneProgress::End(WorkDoneProgressEnd { message }),
+                    )
+                    .await;
+                } else if let (false, Some(message)) = (supported, message) {
+                    client.log_message(MessageType::INFO, message).await;
+                }
+            }
+        }
+    }
+}
+
+async fn send(client: &Client, token: String, value: WorkDoneProgress) {
+    client
+        .send_notification::<Progress>(ProgressParams {
+            token: NumberOrString::String(token),
+            value: ProgressParamsValue::WorkDone(value),
+        })
+        .await;
+}
@@ -1,13 +1,64 @@
-use tower_lsp::lsp_types::{CodeAction, CodeActionKind, CodeActionOrCommand, Command, Url};
+use std::ops;
+
+use parser::Db;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Command, Position, Range, ShowDocumentParams,
+    Url,
+};
 
 use crate::lsp::Context;
 
+/// Editor range of a byte range in a tracked file
+pub fn to_range(db: &Db, uri: &Url, span: ops::Range<usize>) -> Option<Range> {
+    let start = db.get_offset(uri, span.start)?;
+    let end = db.get_offset(uri, span.end)?;
+    Some(Range::new(
+        Position::new(start.0 as u32, start.1 as u32),
+        Position::new(end.0 as u32, end.1 as u32),
+    ))
+}
+
 impl Context {
     pub fn shoud_allow_user(&self, uri: &Url) -> bool {
         let uri = uri.to_string();
         uri.ends_with("/Cargo.toml")
     }
 
+    /// Lets the client open the url, so it ends up on the machine the user sits at.
+    /// Web urls open externally, local paths inside the editor.
+    /// Falls back to opening the browser on the server.
+    pub async fn open_url(&self, url: &str) -> Result<(), String> {
+        let (uri, external) = match Url::parse(url) {
+            Ok(uri) if uri.scheme() == "file" => (Some(uri), false),
+            Ok(uri) => (Some(uri), true),
+            Err(_) => (Url::from_file_path(url).ok(), false),
+        };
+        let supported = self
+            .capabilities
+            .read()
+            .await
+            .window
+            .as_ref()
+            .and_then(|v| v.show_document.as_ref())
+            .map(|v| v.support)
+            .unwrap_or_default();
+        if let (true, Some(uri)) = (supported, uri) {
+            let shown = self
+                .client
+                .show_document(ShowDocumentParams {
+                    uri,
+                    external: Some(external),
+                    take_focus: Some(true),
+                    selection: None,
+                })
+                .await;
+            if let Ok(true) = shown {
+                return Ok(());
+            }
+        }
+        webbrowser::open(url).map_err(|e| e.to_string())
+    }
+
     pub async fn first_line_actions(&self) -> Vec<CodeActionOrCommand> {
         let mut actions = vec![];
         let action = CodeAction {
@@ -0,0 +1,75 @@
+use tower_lsp::lsp_types::{
+    DidChangeWatchedFilesRegistrationOptions, FileChangeType, FileEvent, FileSystemWatcher,
+    GlobPattern, MessageType, Registration,
+};
+
+use crate::lsp::Context;
+
+const WATCHED_FILES: [&str; 3] = ["**/Cargo.lock", "**/Cargo.toml", "**/.cargo/config.toml"];
+
+impl Context {
+    pub async fn register_file_watchers(&self) {
+        let dynamic = self
+            .capabilities
+            .read()
+            .await
+            .workspace
+            .as_ref()
+            .and_then(|v| v.did_change_watched_files)
+            .and_then(|v| v.dynamic_registration)
+            .unwrap_or_default();
+        if !dynamic {
+            return;
+        }
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: WATCHED_FILES
+                .iter()
+                .map(|v| FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(v.to_string()),
+                    kind: None,
+                })
+                .collect(),
+        };
+        let registration = Registration {
+            id: "cargotom-watched-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("failed to register file watchers: {}", e),
+                )
+                .await;
+        }
+    }
+
+    pub async fn watched_files_changed(&self, changes: Vec<FileEvent>) {
+        let mut config_changed = false;
+        let mut lock = self.db.write("did_change_watched_files").await;
+        for change in changes {
+            let uri = change.uri;
+            let path = uri.path();
+            if path.ends_with("/.cargo/config.toml") {
+                config_changed = true;
+            } else if path.ends_with("/Cargo.lock") {
+                match change.typ {
+                    FileChangeType::DELETED => lock.remove_lock(&uri).await,
+                    _ => lock.update_lock(uri).await,
+                }
+            } else if path.ends_with("/Cargo.toml") {
+                match change.typ {
+                    FileChangeType::DELETED => lock.remove_file(&uri).await,
+                    _ => {
+                        lock.reload_from_disk(uri).await;
+                    }
+                }
+            }
+        }
+        if config_changed {
+            self.info.reload_registries().await;
+            lock.analyze(None).await;
+        }
+    }
+}
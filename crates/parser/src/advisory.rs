@@ -0,0 +1,109 @@
+use info_provider::{advisory::Advisory, api::CacheItemOut};
+use rust_version::{RustVersion, VersionReq};
+
+use crate::{
+    toml::{DepSource, Dependency, Positioned},
+    tree::RangeExclusive,
+    Db, Level, Uri,
+};
+
+/// An advisory hit with the release that fixes it and the `cargo update` target, see
+/// [`crate::Warning`]
+pub(crate) type AdvisoryItem = (
+    Level,
+    RangeExclusive,
+    String,
+    Option<String>,
+    Option<String>,
+);
+
+fn message(advisory: &Advisory, name: &str, locked: &str) -> (Level, String) {
+    let (level, kind) = match &advisory.informational {
+        Some(kind) => (Level::Warn, kind.clone()),
+        None => (
+            Level::Error,
+            advisory
+                .severity()
+                .unwrap_or_else(|| "vulnerability".to_owned()),
+        ),
+    };
+    let patched = match advisory.patched.is_empty() {
+        true => "no patched release".to_owned(),
+        false => format!("patched in {}", advisory.patched.join(", ")),
+    };
+    let msg = format!(
+        "{} ({kind}) in {name} {locked}: {}, {patched}",
+        advisory.id, advisory.title
+    );
+    (level, msg)
+}
+
+impl Db {
+    /// Advisories affecting a locked registry release, as (level, message, nearest release
+    /// without the advisory)
+    pub(crate) async fn advisories(
+        &self,
+        registry: Option<&str>,
+        name: &str,
+        locked: &str,
+    ) -> Vec<(Level, String, Option<String>)> {
+        let Ok(version) = RustVersion::try_from(locked) else {
+            return vec![];
+        };
+        let advisories = self.info.advisories(name, &version).await;
+        if advisories.is_empty() {
+            return vec![];
+        }
+        let releases = match self.info.get_info_cache(registry, name).await {
+            CacheItemOut::Ready(releases) => releases,
+            _ => vec![],
+        };
+        advisories
+            .iter()
+            .map(|advisory| {
+                let (level, msg) = message(advisory, name, locked);
+                (level, msg, advisory.nearest_fix(&version, &releases))
+            })
+            .collect()
+    }
+
+    /// Advisories of the locked release of a manifest dependency. The fix is a
+    /// `cargo update --precise` when the requirement allows the patched release, otherwise
+    /// the requirement itself has to change
+    pub(crate) async fn dependency_advisories(
+        &self,
+        uri: &Uri,
+        dep: &Positioned<Dependency>,
+    ) -> Vec<AdvisoryItem> {
+        let Some(package) = self.resolve_dependency(uri, &dep.data) else {
+            return vec![];
+        };
+        if !package.is_registry() {
+            return vec![];
+        }
+        let Some(source) = self.effective_source(uri, &dep.data) else {
+            return vec![];
+        };
+        let requirement = match source {
+            DepSource::Version { value, .. } => {
+                VersionReq::try_from(value.value.data.as_str()).ok()
+            }
+            _ => None,
+        };
+        let name = dep.data.crate_name();
+        let locked = package.version.to_string();
+        self.advisories(source.registry(), &name, &locked)
+            .await
+            .into_iter()
+            .map(|(level, msg, fix)| {
+                let allowed = fix
+                    .as_deref()
+                    .and_then(|v| RustVersion::try_from(v).ok())
+                    .zip(requirement.as_ref())
+                    .is_some_and(|(v, req)| req.matches(&v));
+                let update = allowed.then(|| format!("{name}@{locked}"));
+                (level, dep.data.crate_name_range(), msg, update, fix)
+            })
+            .collect()
+    }
+}
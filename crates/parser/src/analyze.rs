@@ -1,11 +1,23 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap},
+    ops,
+};
 
 use async_recursion::async_recursion;
 use info_provider::api::CacheItemOut;
-use rust_version::RustVersion;
+use rust_version::{RustVersion, VersionReq};
+use serde_json::json;
 use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
 
-use crate::{toml::DepSource, tree::RangeExclusive, Db, Level, Uri, Warning};
+use crate::{
+    structs::lock::Duplicate,
+    toml::{DepSource, Dependency, Positioned},
+    tree::RangeExclusive,
+    Db, Level, Uri, Warning,
+};
+
+/// Package to run `cargo update -p` on and the release that fixes a [`Warning`]
+pub(crate) type Fix = (Option<String>, Option<String>);
 
 impl Db {
     pub async fn analyze(&self, uri: Option<Uri>) {
@@ -19,12 +31,16 @@ impl Db {
         }
     }
     #[async_recursion]
-    async fn analyze_single(&self, uri: &Uri) -> Option<()> {
+    pub(crate) async fn analyze_single(&self, uri: &Uri) -> Option<()> {
+        if self.locks.contains_key(uri) {
+            return self.analyze_lock(uri).await;
+        }
         let toml = self.tomls.get(uri)?;
         let mut errors = vec![];
         let mut warnings = vec![];
         let mut names: HashMap<String, Vec<_>> = HashMap::new();
         let workspace = self.workspaces.get(uri).and_then(|v| self.tomls.get(v));
+        let msrv = self.msrv(uri);
 
         for toml in &toml.dependencies {
             let mut features: HashMap<String, Vec<_>> = HashMap::new();
@@ -123,7 +139,17 @@ impl Db {
                                 .iter()
                                 .filter_map(|v| v.ver().map(|ver| (v, ver)))
                                 .collect::<Vec<_>>();
-                            if let Some((package, ..)) = versions.iter().rfind(|(_, v)| v == &ver) {
+                            // partial versions like `1.0` match any 1.0.x, a yanked release
+                            // is only picked when the requirement names it exactly
+                            let exact = ver.patch().is_some();
+                            let release = versions
+                                .iter()
+                                .rfind(|(p, v)| !p.yanked && v == &ver)
+                                .or_else(|| match exact {
+                                    true => versions.iter().rfind(|(_, v)| v == &ver),
+                                    false => None,
+                                });
+                            if let Some((package, ..)) = release {
                                 let all_features = package.feature_all();
                                 for feature in &toml.data.features.data {
                                     if !all_features.contains(&feature.data) {
@@ -133,8 +159,15 @@ impl Db {
                                         ))
                                     }
                                 }
+                                if package.yanked {
+                                    warnings.push((
+                                        range,
+                                        format!("Version {} is yanked", package.vers),
+                                    ));
+                                }
                                 if let Some((info, _)) = versions
                                     .iter()
+                                    .filter(|v| !v.0.yanked && v.0.supports_rust(msrv.as_ref()))
                                     .filter(|v| match self.config.stable_version {
                                         true => !v.1.is_pre_release(),
                                         false => true,
@@ -148,6 +181,14 @@ impl Db {
                                         ))
                                     }
                                 }
+                            } else if versions.iter().any(|(_, v)| v == &ver) {
+                                warnings.push((
+                                    range,
+                                    format!(
+                                        "Every release matching {} is yanked",
+                                        value.value.data
+                                    ),
+                                ));
                             } else {
                                 errors.push((range, "Invalid version".to_string()))
                             }
@@ -169,30 +210,96 @@ impl Db {
                 }
             }
         }
+        let mut lock_warnings = vec![];
+        let mut advisories = vec![];
+        if let Some(lock) = self.get_lock(uri) {
+            if self.config.advisory_warnings {
+                for dep in &toml.dependencies {
+                    advisories.extend(self.dependency_advisories(uri, dep).await);
+                }
+            }
+            for dep in &toml.dependencies {
+                if let Some((range, msg, update)) = self.check_lock(uri, dep) {
+                    lock_warnings.push((Level::Warn, range, msg, update));
+                } else {
+                    lock_warnings.extend(self.check_locked_release(uri, dep, msrv.as_ref()).await);
+                }
+            }
+            if let (true, Some(member)) = (self.config.duplicate_warnings, &toml.name) {
+                let duplicates = lock.duplicates();
+                for dep in &toml.dependencies {
+                    for (range, msg) in duplicate_infos(duplicates, &member.data, &dep.data) {
+                        lock_warnings.push((Level::Info, range, msg, None));
+                    }
+                }
+            }
+        }
+
+        if self.config.unification_warnings && !toml.workspace {
+            for dep in &toml.dependencies {
+                let gaps = self.unification_gaps(uri, &dep.data);
+                if gaps.is_empty() {
+                    continue;
+                }
+                let features = gaps.keys().cloned().collect::<Vec<_>>().join(", ");
+                let members = gaps
+                    .into_values()
+                    .flatten()
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let range = match dep.data.features.data.is_empty() {
+                    true => dep.data.crate_name_range(),
+                    false => RangeExclusive::from(&dep.data.features),
+                };
+                lock_warnings.push((
+                    Level::Warn,
+                    range,
+                    format!(
+                        "{} features {features} are only enabled by {members}, this member gets them through feature unification",
+                        dep.data.crate_name()
+                    ),
+                    None,
+                ));
+            }
+        }
+
+        if self.config.family_warnings {
+            for family in self.families(uri).await {
+                for dep in &toml.dependencies {
+                    let name = dep.data.crate_name();
+                    let Some(conflict) = family.member(&name).and_then(|v| v.conflict.as_ref())
+                    else {
+                        continue;
+                    };
+                    warnings.push((
+                        dep.data.crate_name_range(),
+                        format!(
+                            "{name} is out of step with the {} family: {conflict}",
+                            family.name
+                        ),
+                    ));
+                }
+            }
+        }
+
         let mut warn = vec![];
         for (range, msg) in warnings {
-            let start = self.get_offset(uri, range.start as usize);
-            let end = self.get_offset(uri, range.end as usize);
-            if let (Some(start), Some(end)) = (start, end) {
-                warn.push(Warning {
-                    level: crate::Level::Warn,
-                    msg,
-                    start,
-                    end,
-                });
-            }
+            let range = range.start as usize..range.end as usize;
+            self.push_warning(&mut warn, uri, range, Level::Warn, msg, (None, None));
+        }
+        for (level, range, msg, update) in lock_warnings {
+            let range = range.start as usize..range.end as usize;
+            self.push_warning(&mut warn, uri, range, level, msg, (update, None));
+        }
+        for (level, range, msg, update, precise) in advisories {
+            let range = range.start as usize..range.end as usize;
+            self.push_warning(&mut warn, uri, range, level, msg, (update, precise));
         }
         for (range, msg) in errors {
-            let start = self.get_offset(uri, range.start as usize);
-            let end = self.get_offset(uri, range.end as usize);
-            if let (Some(start), Some(end)) = (start, end) {
-                warn.push(Warning {
-                    level: crate::Level::Error,
-                    msg,
-                    start,
-                    end,
-                });
-            }
+            let range = range.start as usize..range.end as usize;
+            self.push_warning(&mut warn, uri, range, Level::Error, msg, (None, None));
         }
         let lock = self.warnings.write();
         lock.await.insert(uri.clone(), warn.clone());
@@ -206,9 +313,196 @@ impl Db {
             .await;
         Some(())
     }
+
+    /// Adds a warning over a byte range of the file, dropped when the range isn't in it
+    pub(crate) fn push_warning(
+        &self,
+        warn: &mut Vec<Warning>,
+        uri: &Uri,
+        range: ops::Range<usize>,
+        level: Level,
+        msg: String,
+        (update, precise): Fix,
+    ) {
+        let start = self.get_offset(uri, range.start);
+        let end = self.get_offset(uri, range.end);
+        if let (Some(start), Some(end)) = (start, end) {
+            warn.push(Warning {
+                level,
+                msg,
+                start,
+                end,
+                update,
+                precise,
+            });
+        }
+    }
 }
 
-fn to_diagnostics(hide_docs_info_message: bool, items: Vec<Warning>) -> Vec<Diagnostic> {
+impl Db {
+    /// Cross checks a dependency with the Cargo.lock, returns the range, message and the
+    /// package `cargo update -p` needs to run on
+    fn check_lock(
+        &self,
+        uri: &Uri,
+        dep: &Positioned<Dependency>,
+    ) -> Option<(RangeExclusive, String, Option<String>)> {
+        let lock = self.get_lock(uri)?;
+        let toml = self.tomls.get(uri)?;
+        let member = toml.name.as_ref().map(|v| v.data.clone());
+        let name = dep.data.crate_name();
+        // `[workspace.dependencies]` only end up in the lock once a member uses them
+        if toml.workspace && lock.graph().find(&name).is_empty() {
+            return None;
+        }
+        let missing = match (&member, toml.workspace) {
+            (Some(member), false) => lock.depends_on(member, &name) == Some(false),
+            _ => false,
+        };
+        let package = match missing {
+            true => None,
+            false => self.resolve_dependency(uri, &dep.data),
+        };
+        let Some(package) = package else {
+            // new dependencies are picked up when the member itself is updated
+            return Some((
+                dep.data.crate_name_range(),
+                format!("{name} is missing from Cargo.lock"),
+                member,
+            ));
+        };
+        let (requirement, range) = match &dep.data.source {
+            DepSource::Version { value, .. } => {
+                (value.value.data.clone(), dep.data.source.range()?)
+            }
+            DepSource::Workspace(range) => {
+                let workspace = self.get_workspace(uri).and_then(|v| self.tomls.get(v))?;
+                let dep = workspace
+                    .dependencies
+                    .iter()
+                    .find(|v| v.data.name() == dep.data.name())?;
+                match &dep.data.source {
+                    DepSource::Version { value, .. } => (value.value.data.clone(), *range),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        let req = VersionReq::try_from(requirement.as_str()).ok()?;
+        let locked = package.version.to_string();
+        let version = RustVersion::try_from(locked.as_str()).ok()?;
+        if req.matches(&version) {
+            return None;
+        }
+        Some((
+            range,
+            format!("Locked version {locked} does not satisfy \"{requirement}\""),
+            Some(format!("{name}@{locked}")),
+        ))
+    }
+}
+
+impl Db {
+    /// Warns when the lock pins a release that was yanked since or that needs a newer Rust
+    /// than the package declares. Only looks at the index cache, the manifest analysis
+    /// fetches it
+    async fn check_locked_release(
+        &self,
+        uri: &Uri,
+        dep: &Positioned<Dependency>,
+        msrv: Option<&RustVersion>,
+    ) -> Vec<(Level, RangeExclusive, String, Option<String>)> {
+        self.locked_release_warnings(uri, dep, msrv)
+            .await
+            .unwrap_or_default()
+    }
+
+    async fn locked_release_warnings(
+        &self,
+        uri: &Uri,
+        dep: &Positioned<Dependency>,
+        msrv: Option<&RustVersion>,
+    ) -> Option<Vec<(Level, RangeExclusive, String, Option<String>)>> {
+        let package = self.resolve_dependency(uri, &dep.data)?;
+        if !package.is_registry() {
+            return None;
+        }
+        let name = dep.data.crate_name();
+        let registry = match &dep.data.source {
+            DepSource::Version { registry, .. } => registry.as_ref().map(|v| v.value.data.clone()),
+            _ => None,
+        };
+        let CacheItemOut::Ready(versions) =
+            self.info.get_info_cache(registry.as_deref(), &name).await
+        else {
+            return None;
+        };
+        let locked = package.version.to_string();
+        let release = versions.iter().find(|v| v.vers == locked)?;
+        let mut out = vec![];
+        if release.yanked {
+            out.push((
+                Level::Warn,
+                dep.data.crate_name_range(),
+                format!("Cargo.lock pins {name} {locked}, which is yanked"),
+                Some(format!("{name}@{locked}")),
+            ));
+        }
+        if let (false, Some(msrv), Some(required)) = (
+            release.supports_rust(msrv),
+            msrv,
+            release.rust_version.as_ref(),
+        ) {
+            out.push((
+                Level::Warn,
+                dep.data.crate_name_range(),
+                format!(
+                    "Locked {name} {locked} needs Rust {required}, the package declares {msrv}"
+                ),
+                None,
+            ));
+        }
+        Some(out)
+    }
+}
+
+/// Copies of duplicated packages the dependency pulls into the member
+fn duplicate_infos(
+    duplicates: &[Duplicate],
+    member: &str,
+    dep: &Dependency,
+) -> Vec<(RangeExclusive, String)> {
+    let name = dep.crate_name();
+    let mut out = vec![];
+    for duplicate in duplicates {
+        for version in &duplicate.versions {
+            let pulled = version
+                .pulled_in_by
+                .iter()
+                .any(|v| v.member == member && v.dependency == name);
+            if !pulled {
+                continue;
+            }
+            let others = duplicate
+                .versions
+                .iter()
+                .filter(|v| v.version != version.version)
+                .map(|v| v.version.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            out.push((
+                dep.crate_name_range(),
+                format!(
+                    "Pulls in {} {}, which is also locked at {others}",
+                    duplicate.name, version.version
+                ),
+            ));
+        }
+    }
+    out
+}
+
+pub(crate) fn to_diagnostics(hide_docs_info_message: bool, items: Vec<Warning>) -> Vec<Diagnostic> {
     let mut d = match hide_docs_info_message {
         false => vec![
             Diagnostic {
@@ -246,6 +540,7 @@ fn to_diagnostics(hide_docs_info_message: bool, items: Vec<Warning>) -> Vec<Diag
                 },
             },
             severity: Some(match item.level {
+                Level::Info => DiagnosticSeverity::INFORMATION,
                 Level::Warn => DiagnosticSeverity::WARNING,
                 Level::Error => DiagnosticSeverity::ERROR,
             }),
@@ -255,7 +550,10 @@ fn to_diagnostics(hide_docs_info_message: bool, items: Vec<Warning>) -> Vec<Diag
             message: item.msg,
             related_information: None,
             tags: None,
-            data: None,
+            data: match (item.update, item.precise) {
+                (None, None) => None,
+                (update, precise) => Some(json!({ "cargo-update": update, "precise": precise })),
+            },
         });
     }
     d
@@ -24,6 +24,29 @@ pub struct Config {
 
     #[serde(default = "default_true")]
     pub outdated_crate_warnings: bool,
+
+    /// Hint at dependencies that pull in packages locked at several versions
+    #[serde(default = "default_true")]
+    pub duplicate_warnings: bool,
+
+    /// Warn when a member only gets features of a dependency because another workspace
+    /// member enables them
+    #[serde(default = "default_true")]
+    pub unification_warnings: bool,
+
+    /// Warn when crates that are released together, like `serde` and `serde_derive`, are
+    /// out of step
+    #[serde(default = "default_true")]
+    pub family_warnings: bool,
+
+    /// Report locked releases with RustSec advisories
+    #[serde(default = "default_true")]
+    pub advisory_warnings: bool,
+
+    /// Local clone of the RustSec advisory-db, by default a copy is downloaded into the
+    /// storage folder
+    #[serde(default)]
+    pub advisory_db: Option<String>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -73,6 +96,11 @@ impl Default for Config {
             stable_version: true,
             offline: true,
             outdated_crate_warnings: true,
+            duplicate_warnings: true,
+            unification_warnings: true,
+            family_warnings: true,
+            advisory_warnings: true,
+            advisory_db: None,
         }
     }
 }
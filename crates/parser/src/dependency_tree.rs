@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::{
+    structs::lock::CargoLock,
+    toml::{DependencyKind, Positioned, Target},
+    Db, Uri,
+};
+
+/// A node of the resolved dependency graph, as shown in editor tree views
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeNode {
+    pub name: String,
+    /// None if the package isn't in the lock file
+    pub version: Option<String>,
+    pub source: Option<String>,
+    /// Only known for direct dependencies
+    pub kind: Option<DependencyKind>,
+    /// `cfg(...)` or target triple of the target table
+    pub target: Option<String>,
+    /// Features the manifest enables, including `default`
+    pub features: Vec<String>,
+    pub optional: bool,
+    /// The package is locked at more than one version
+    pub duplicate: bool,
+    /// `workspace = true`
+    pub workspace: bool,
+    /// Already expanded elsewhere in the tree
+    pub repeated: bool,
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            version: None,
+            source: None,
+            kind: None,
+            target: None,
+            features: vec![],
+            optional: false,
+            duplicate: false,
+            workspace: false,
+            repeated: false,
+            children: vec![],
+        }
+    }
+}
+
+impl Db {
+    /// Resolved dependency tree of a manifest built from its Cargo.lock
+    pub fn dependency_tree(&self, uri: &Uri) -> Option<TreeNode> {
+        let toml = self.tomls.get(uri)?;
+        let lock = self.get_lock(uri);
+        let member = toml.name.as_ref().map(|v| v.data.clone());
+        let root = lock.and_then(|lock| {
+            let member = member.as_deref()?;
+            let index = *lock
+                .graph()
+                .find(member)
+                .iter()
+                .find(|i| lock.entry(**i).is_some_and(|v| v.is_member()))?;
+            Some((lock, index))
+        });
+        let mut seen = HashSet::new();
+        if let Some((_, index)) = root {
+            seen.insert(index);
+        }
+        let children = toml
+            .dependencies
+            .iter()
+            .map(|dep| {
+                let inherited = self.inherited(uri, &dep.data);
+                let index = self.resolve_dependency_index(uri, &dep.data);
+                let mut features = dep
+                    .data
+                    .features
+                    .data
+                    .iter()
+                    .chain(inherited.iter().flat_map(|v| &v.features.data))
+                    .map(|v| v.data.clone())
+                    .collect::<Vec<_>>();
+                let default_features = [Some(&dep.data), inherited]
+                    .into_iter()
+                    .flatten()
+                    .all(|v| v.default_features.as_ref().map(|v| v.data) != Some(false));
+                if default_features {
+                    features.insert(0, "default".to_owned());
+                }
+                features.dedup();
+                let mut node = match (lock, index) {
+                    (Some(lock), Some(index)) => locked_node(lock, index, &mut seen),
+                    _ => TreeNode::new(dep.data.crate_name()),
+                };
+                node.kind = Some(dep.data.kind);
+                node.target = target(&dep.data.target);
+                node.features = features;
+                node.optional = dep.data.optional.as_ref().is_some_and(|v| v.data);
+                node.workspace = inherited.is_some();
+                node
+            })
+            .collect();
+        Some(TreeNode {
+            version: root.and_then(|(lock, i)| Some(lock.entry(i)?.version.clone())),
+            children,
+            ..TreeNode::new(member.unwrap_or_default())
+        })
+    }
+}
+
+fn target(target: &[Positioned<Target>]) -> Option<String> {
+    match target.is_empty() {
+        true => None,
+        false => Some(
+            target
+                .iter()
+                .map(|v| v.data.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+    }
+}
+
+/// Transitive packages are expanded once, like `cargo tree` does
+fn locked_node(lock: &CargoLock, index: usize, seen: &mut HashSet<usize>) -> TreeNode {
+    let entry = lock.entry(index);
+    let name = entry.map(|v| v.name.clone()).unwrap_or_default();
+    let mut node = TreeNode {
+        duplicate: lock.graph().find(&name).len() > 1,
+        version: entry.map(|v| v.version.clone()),
+        source: entry.and_then(|v| v.source.clone()),
+        ..TreeNode::new(name)
+    };
+    if !seen.insert(index) {
+        node.repeated = !lock.graph().dependencies(index).is_empty();
+        return node;
+    }
+    node.children = lock
+        .graph()
+        .dependencies(index)
+        .iter()
+        .map(|i| locked_node(lock, *i, seen))
+        .collect();
+    node
+}
@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+
+use info_provider::api::{CacheItemOut, Root1};
+use rust_version::RustVersion;
+
+use crate::{toml::DepSource, Db, Uri};
+
+/// Dependencies of a manifest that are released together and have to be upgraded together.
+/// Linked either by an exact `=` requirement in the index data or by a shared name prefix
+/// with the same recent releases, like `bevy_*` or `wasm-bindgen-*`
+#[derive(Debug, Clone)]
+pub struct Family {
+    /// Shortest crate name of the family
+    pub name: String,
+    pub members: Vec<FamilyMember>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FamilyMember {
+    pub crate_name: String,
+    /// Release the dependency uses, see [`Db::dependency_release`]
+    pub current: String,
+    /// Release to move to, the pin of another member's target wins over the newest release
+    pub target: String,
+    /// Why the member is out of step with the rest of the family
+    pub conflict: Option<String>,
+}
+
+impl Family {
+    pub fn contains(&self, crate_name: &str) -> bool {
+        self.members.iter().any(|v| v.crate_name == crate_name)
+    }
+
+    pub fn member(&self, crate_name: &str) -> Option<&FamilyMember> {
+        self.members.iter().find(|v| v.crate_name == crate_name)
+    }
+}
+
+struct Entry {
+    name: String,
+    current: Root1,
+    target: Root1,
+    /// Versions of the last [`LOCKSTEP`] releases
+    recent: Vec<String>,
+}
+
+/// Releases crates with a shared prefix must have in common to count as a family, a single
+/// matching version is often a coincidence
+const LOCKSTEP: usize = 3;
+
+impl Db {
+    /// Lockstep families among the registry dependencies of a manifest. Only cached index
+    /// data is used
+    pub async fn families(&self, uri: &Uri) -> Vec<Family> {
+        let Some(toml) = self.tomls.get(uri) else {
+            return vec![];
+        };
+        let msrv = self.msrv(uri);
+        let mut entries: Vec<Entry> = vec![];
+        for dep in &toml.dependencies {
+            let name = dep.data.crate_name();
+            if entries.iter().any(|v| v.name == name) {
+                continue;
+            }
+            let Some(source @ DepSource::Version { .. }) = self.effective_source(uri, &dep.data)
+            else {
+                continue;
+            };
+            let CacheItemOut::Ready(versions) =
+                self.info.get_info_cache(source.registry(), &name).await
+            else {
+                continue;
+            };
+            let Some(current) = self.dependency_release(uri, &dep.data).await else {
+                continue;
+            };
+            let recent = versions
+                .iter()
+                .rev()
+                .take(LOCKSTEP)
+                .map(|v| v.vers.clone())
+                .collect();
+            let pre = current.ver().is_some_and(|v| v.is_pre_release());
+            let target = versions.into_iter().rev().find(|v| {
+                !v.yanked
+                    && v.supports_rust(msrv.as_ref())
+                    && (pre
+                        || !self.config.stable_version
+                        || v.ver().is_some_and(|v| !v.is_pre_release()))
+            });
+            entries.push(Entry {
+                name,
+                target: target.unwrap_or_else(|| current.clone()),
+                current,
+                recent,
+            });
+        }
+        group(entries)
+    }
+}
+
+/// Exact requirements of a release on other crates of `entries`, as (index, version)
+fn pins(release: &Root1, entries: &[Entry]) -> Vec<(usize, String)> {
+    release
+        .deps
+        .iter()
+        .filter_map(|dep| {
+            let version = dep.req.trim().strip_prefix('=')?.trim();
+            let name = dep.package.as_ref().unwrap_or(&dep.name);
+            let index = entries.iter().position(|v| &v.name == name)?;
+            Some((index, version.to_owned()))
+        })
+        .collect()
+}
+
+fn prefix(name: &str) -> &str {
+    name.split(['-', '_']).next().unwrap_or(name)
+}
+
+/// Published together for the last [`LOCKSTEP`] releases
+fn lockstep(a: &Entry, b: &Entry) -> bool {
+    a.recent.len() == LOCKSTEP && a.recent == b.recent
+}
+
+fn group(entries: Vec<Entry>) -> Vec<Family> {
+    let mut parent = (0..entries.len()).collect::<Vec<_>>();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut union = |a: usize, b: usize| {
+        let (a, b) = (root(&mut parent, a), root(&mut parent, b));
+        parent[a] = b;
+    };
+    for (a, entry) in entries.iter().enumerate() {
+        for (b, _) in pins(&entry.current, &entries)
+            .into_iter()
+            .chain(pins(&entry.target, &entries))
+        {
+            union(a, b);
+        }
+        for (b, other) in entries.iter().enumerate().skip(a + 1) {
+            if prefix(&entry.name) == prefix(&other.name) && lockstep(entry, other) {
+                union(a, b);
+            }
+        }
+    }
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..entries.len() {
+        groups.entry(root(&mut parent, i)).or_default().push(i);
+    }
+    groups
+        .into_values()
+        .filter(|v| v.len() > 1)
+        .map(|indices| family(&entries, &indices))
+        .collect()
+}
+
+fn family(entries: &[Entry], indices: &[usize]) -> Family {
+    let mut members = indices
+        .iter()
+        .map(|i| FamilyMember {
+            crate_name: entries[*i].name.clone(),
+            current: entries[*i].current.vers.clone(),
+            target: entries[*i].target.vers.clone(),
+            conflict: None,
+        })
+        .collect::<Vec<_>>();
+    let position = |index: usize| indices.iter().position(|v| *v == index);
+    for i in indices {
+        let entry = &entries[*i];
+        for (pinned, version) in pins(&entry.target, entries) {
+            if let Some(pos) = position(pinned) {
+                members[pos].target = version;
+            }
+        }
+        for (pinned, version) in pins(&entry.current, entries) {
+            let Some(pos) = position(pinned) else {
+                continue;
+            };
+            if members[pos].current != version {
+                members[pos].conflict = Some(format!(
+                    "{} {} requires {} ={version}",
+                    entry.name, entry.current.vers, members[pos].crate_name
+                ));
+            }
+        }
+    }
+    // crates that always share a version number
+    let shared = members.windows(2).all(|v| v[0].target == v[1].target);
+    if shared {
+        let newest = members
+            .iter()
+            .filter_map(|v| Some((RustVersion::try_from(v.current.as_str()).ok()?, v)))
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, v)| (v.crate_name.clone(), v.current.clone()));
+        if let Some((name, version)) = newest {
+            for member in members.iter_mut().filter(|v| v.current != version) {
+                member.conflict.get_or_insert_with(|| {
+                    format!("{name} is already at {version}, these crates are released together")
+                });
+            }
+        }
+    }
+    let name = members
+        .iter()
+        .map(|v| v.crate_name.as_str())
+        .min_by_key(|v| v.len())
+        .unwrap_or_default()
+        .to_owned();
+    Family { name, members }
+}
+
+#[cfg(test)]
+mod tests {
+    use info_provider::api::{Deps1, Root1};
+
+    use super::{group, Entry};
+
+    fn release(name: &str, vers: &str, pins: &[(&str, &str)]) -> Root1 {
+        let mut release = Root1::new(name, vers);
+        for (name, req) in pins {
+            release.deps.push(Deps1 {
+                optional: true,
+                ..Deps1::new(name, req)
+            });
+        }
+        release
+    }
+
+    fn entry(name: &str, current: Root1, target: Root1, recent: &[&str]) -> Entry {
+        Entry {
+            name: name.to_owned(),
+            current,
+            target,
+            recent: recent.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn families() {
+        let bevy = ["0.13.1", "0.13.2", "0.14.0"];
+        let entries = vec![
+            entry(
+                "serde",
+                release("serde", "1.0.200", &[("serde_derive", "=1.0.200")]),
+                release("serde", "1.0.210", &[("serde_derive", "=1.0.210")]),
+                &["1.0.208", "1.0.209", "1.0.210"],
+            ),
+            entry(
+                "serde_derive",
+                release("serde_derive", "1.0.190", &[]),
+                release("serde_derive", "1.0.210", &[]),
+                &["1.0.208", "1.0.209", "1.0.210"],
+            ),
+            entry(
+                "serde_json",
+                release("serde_json", "1.0.100", &[]),
+                release("serde_json", "1.0.128", &[]),
+                &["1.0.126", "1.0.127", "1.0.128"],
+            ),
+            entry(
+                "bevy_ecs",
+                release("bevy_ecs", "0.13.2", &[]),
+                release("bevy_ecs", "0.14.0", &[]),
+                &bevy,
+            ),
+            entry(
+                "bevy_render",
+                release("bevy_render", "0.14.0", &[]),
+                release("bevy_render", "0.14.0", &[]),
+                &bevy,
+            ),
+            // same newest version by coincidence
+            entry(
+                "async-trait",
+                release("async-trait", "0.1.80", &[]),
+                release("async-trait", "0.1.80", &[]),
+                &["0.1.78", "0.1.79", "0.1.80"],
+            ),
+            entry(
+                "async-stream",
+                release("async-stream", "0.1.80", &[]),
+                release("async-stream", "0.1.80", &[]),
+                &["0.1.2", "0.1.50", "0.1.80"],
+            ),
+        ];
+        let families = group(entries);
+        assert_eq!(families.len(), 2);
+        let serde = families.iter().find(|v| v.name == "serde").unwrap();
+        assert!(!serde.contains("serde_json"));
+        let derive = serde.member("serde_derive").unwrap();
+        assert_eq!(derive.target, "1.0.210");
+        assert!(derive.conflict.is_some());
+        assert!(serde.member("serde").unwrap().conflict.is_none());
+        let bevy = families.iter().find(|v| v.name == "bevy_ecs").unwrap();
+        assert!(bevy.member("bevy_ecs").unwrap().conflict.is_some());
+        assert!(bevy.member("bevy_render").unwrap().conflict.is_none());
+    }
+}
@@ -0,0 +1,470 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::read_to_string,
+    path::PathBuf,
+};
+
+use info_provider::{
+    api::{CacheItemOut, Deps1, Root1},
+    features::{FeatureTree, ResolvedFeatures},
+    source::{git_checkout, registry_source},
+};
+use rust_version::{RustVersion, VersionReq};
+
+use crate::{
+    structs::feat::feature_docs,
+    toml::{DepSource, Dependency, DependencyKind},
+    Db, Uri,
+};
+
+/// Manifest, crate, version, enabled features and default features of a cached
+/// [`FeatureTree`]
+pub(crate) type FeatureTreeKey = (Uri, String, String, Vec<String>, bool);
+/// Manifest, crate name and version
+pub(crate) type FeatureDocsKey = (Uri, String, String);
+
+/// What turning on a single feature brings in
+#[derive(Debug, Clone)]
+pub struct FeatureExpansion {
+    /// Sub-features, optional dependencies and dependency features of the feature alone
+    pub resolved: ResolvedFeatures,
+    /// Crates in the build with the feature on
+    pub tree: FeatureTree,
+    /// Crates that are only in the build because of the feature
+    pub added: BTreeSet<String>,
+    /// The part of `added` that Cargo.lock doesn't contain yet
+    pub not_locked: BTreeSet<String>,
+}
+
+/// How another workspace member requests a shared dependency
+#[derive(Debug, Clone)]
+pub struct MemberRequest {
+    pub member: String,
+    pub features: BTreeSet<String>,
+    pub default_features: bool,
+}
+
+impl Db {
+    /// Manifests of the workspace `uri` belongs to, the root included
+    pub fn workspace_manifests(&self, uri: &Uri) -> Vec<&Uri> {
+        let root = self.get_workspace(uri).unwrap_or(uri);
+        self.tomls
+            .keys()
+            .filter(|v| *v == root || self.get_workspace(v) == Some(root))
+            .collect()
+    }
+
+    /// Requests of the other workspace members for the same crate and dependency kind.
+    /// Cargo builds the crate once with the union of them
+    pub fn member_requests(&self, uri: &Uri, dep: &Dependency) -> Vec<MemberRequest> {
+        let name = dep.crate_name();
+        let mut out = vec![];
+        for member in self.workspace_manifests(uri) {
+            if member == uri {
+                continue;
+            }
+            // the root only holds `[workspace.dependencies]`
+            let Some(toml) = self.tomls.get(member).filter(|v| !v.workspace) else {
+                continue;
+            };
+            for other in &toml.dependencies {
+                if other.data.crate_name() != name || other.data.kind != dep.kind {
+                    continue;
+                }
+                let (features, default_features) = self.requested_features(member, &other.data);
+                out.push(MemberRequest {
+                    member: self.member_name(member),
+                    features: features.into_iter().collect(),
+                    default_features,
+                });
+            }
+        }
+        out
+    }
+
+    fn member_name(&self, uri: &Uri) -> String {
+        match self.tomls.get(uri).and_then(|v| v.name.as_ref()) {
+            Some(name) => name.data.clone(),
+            None => uri
+                .path_segments()
+                .and_then(|v| v.rev().nth(1))
+                .unwrap_or_default()
+                .to_owned(),
+        }
+    }
+
+    /// Features of a dependency other members turn on that this member doesn't, with the
+    /// members enabling them. Falls back to the requested lists without index data
+    pub async fn features_elsewhere(
+        &self,
+        uri: &Uri,
+        dep: &Dependency,
+    ) -> BTreeMap<String, BTreeSet<String>> {
+        let release = self.dependency_release(uri, dep).await;
+        let (features, default_features) = self.requested_features(uri, dep);
+        let resolve =
+            |features: &mut dyn Iterator<Item = &str>, default_features: bool| match &release {
+                Some(release) => {
+                    release
+                        .resolve_features(features, default_features)
+                        .features
+                }
+                None => features.map(ToOwned::to_owned).collect(),
+            };
+        let own = resolve(&mut features.iter().map(String::as_str), default_features);
+        let mut out: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for request in self.member_requests(uri, dep) {
+            let theirs = resolve(
+                &mut request.features.iter().map(String::as_str),
+                request.default_features,
+            );
+            for feature in theirs.difference(&own) {
+                out.entry(feature.clone())
+                    .or_default()
+                    .insert(request.member.clone());
+            }
+        }
+        out
+    }
+
+    /// Members whose explicit feature list is a strict subset of another member's for the
+    /// same dependency. They only get the missing features through unification
+    pub fn unification_gaps(
+        &self,
+        uri: &Uri,
+        dep: &Dependency,
+    ) -> BTreeMap<String, BTreeSet<String>> {
+        let (features, _) = self.requested_features(uri, dep);
+        let own = features.into_iter().collect::<BTreeSet<_>>();
+        let mut out: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for request in self.member_requests(uri, dep) {
+            if !own.is_subset(&request.features) || own == request.features {
+                continue;
+            }
+            for feature in request.features.difference(&own) {
+                out.entry(feature.clone())
+                    .or_default()
+                    .insert(request.member.clone());
+            }
+        }
+        out
+    }
+
+    /// Features requested for a dependency and whether the defaults stay on.
+    /// `workspace = true` dependencies add the features of the workspace entry
+    pub fn requested_features(&self, uri: &Uri, dep: &Dependency) -> (Vec<String>, bool) {
+        let inherited = self.inherited(uri, dep);
+        let features = dep
+            .features
+            .data
+            .iter()
+            .chain(inherited.iter().flat_map(|v| &v.features.data))
+            .map(|v| v.data.clone())
+            .collect();
+        let default_features = inherited
+            .unwrap_or(dep)
+            .default_features
+            .map(|v| v.data)
+            .unwrap_or(true);
+        (features, default_features)
+    }
+
+    /// Index entry of the release a dependency uses: the locked one, or the newest match of
+    /// the requirement. Only cached index data is used
+    pub async fn dependency_release(&self, uri: &Uri, dep: &Dependency) -> Option<Root1> {
+        let source = self.effective_source(uri, dep)?;
+        let DepSource::Version { value, .. } = source else {
+            return None;
+        };
+        let CacheItemOut::Ready(versions) = self
+            .info
+            .get_info_cache(source.registry(), &dep.crate_name())
+            .await
+        else {
+            return None;
+        };
+        if let Some(locked) = self.resolve_dependency(uri, dep) {
+            let locked = locked.version.to_string();
+            if let Some(release) = versions.iter().find(|v| v.vers == locked) {
+                return Some(release.clone());
+            }
+        }
+        let req = VersionReq::try_from(value.value.data.as_str()).ok()?;
+        versions.into_iter().rev().find(|v| {
+            !v.yanked
+                && RustVersion::try_from(v.vers.as_str())
+                    .map(|v| req.matches(&v))
+                    .unwrap_or(false)
+        })
+    }
+
+    /// Inlay hints behind `features = [...]` listing what the request implies on top
+    pub async fn feature_hints(&self, uri: &Uri) -> Option<Vec<((usize, usize), String)>> {
+        let toml = self.tomls.get(uri)?;
+        let mut out = vec![];
+        for dep in &toml.dependencies {
+            if dep.data.features.data.is_empty() {
+                continue;
+            }
+            let Some(resolved) = self.dependency_features(uri, &dep.data).await else {
+                continue;
+            };
+            let requested = dep
+                .data
+                .features
+                .data
+                .iter()
+                .map(|v| v.data.as_str())
+                .collect::<Vec<_>>();
+            let implied = resolved
+                .features
+                .iter()
+                .filter(|v| {
+                    !requested.contains(&v.as_str()) && !resolved.optional_deps.contains(*v)
+                })
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            let mut parts = vec![];
+            if !implied.is_empty() {
+                parts.push(format!("enables {}", short_list(&implied)));
+            }
+            let optional = resolved
+                .optional_deps
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            if !optional.is_empty() {
+                parts.push(format!("pulls in {}", short_list(&optional)));
+            }
+            if parts.is_empty() {
+                continue;
+            }
+            if let Some(pos) = self.get_offset(uri, dep.data.features.end as usize) {
+                out.push((pos, parts.join("; ")));
+            }
+        }
+        Some(out)
+    }
+
+    /// The manifest's own `[features]` and crates.io dependencies in the shape of an index
+    /// entry, so they can be resolved like a published release. Path, git and other
+    /// registry dependencies are left out, their names could match unrelated crates.io ones
+    pub fn manifest_release(&self, uri: &Uri) -> Option<Root1> {
+        let toml = self.tomls.get(uri)?;
+        let features = toml
+            .features
+            .iter()
+            .map(|v| {
+                let args = v.data.args.iter().map(ToString::to_string).collect();
+                (v.data.name.data.clone(), args)
+            })
+            .collect::<HashMap<_, _>>();
+        let deps = toml
+            .dependencies
+            .iter()
+            .filter_map(|v| {
+                let req = match self.effective_source(uri, &v.data)? {
+                    DepSource::Version {
+                        value,
+                        registry: None,
+                    } => value.value.data.clone(),
+                    _ => return None,
+                };
+                let (features, default_features) = self.requested_features(uri, &v.data);
+                Some(Deps1 {
+                    name: v.data.name().to_owned(),
+                    req,
+                    optional: v.data.optional.map(|v| v.data).unwrap_or_default(),
+                    features,
+                    default_features,
+                    kind: Some(
+                        match v.data.kind {
+                            DependencyKind::Normal => "normal",
+                            DependencyKind::Development => "dev",
+                            DependencyKind::Build => "build",
+                        }
+                        .to_owned(),
+                    ),
+                    package: v.data.package.as_ref().map(|v| v.data.clone()),
+                })
+            })
+            .collect();
+        let name = toml
+            .name
+            .as_ref()
+            .map(|v| v.data.as_str())
+            .unwrap_or_default();
+        Some(Root1 {
+            deps,
+            features,
+            ..Root1::new(name, "0.0.0")
+        })
+    }
+
+    /// Expands `feature` of `release` and compares the crates it needs with the build
+    /// without it. `others` are the features that stay enabled either way. Only cached
+    /// index data is used, crates without it are fetched in the background and listed in
+    /// [`FeatureTree::missing`]. The build without the feature is cached per dependency
+    pub async fn expand_feature(
+        &self,
+        uri: &Uri,
+        registry: Option<&str>,
+        release: &Root1,
+        feature: &str,
+        others: &[String],
+        default_features: bool,
+    ) -> FeatureExpansion {
+        let resolved = release.resolve_features([feature], false);
+        let without = others
+            .iter()
+            .filter(|v| *v != feature)
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut with = without.clone();
+        with.push(feature.to_owned());
+        // only cached index data, this runs while the hover holds the Db
+        let tree = self
+            .info
+            .resolve_feature_tree(registry, release, &with, default_features, false)
+            .await;
+        let key = (
+            uri.clone(),
+            release.name.clone(),
+            release.vers.clone(),
+            without.clone(),
+            default_features,
+        );
+        let cached = self.feature_trees.read().await.get(&key).cloned();
+        let base = match cached {
+            Some(base) => base,
+            None => {
+                let base = self
+                    .info
+                    .resolve_feature_tree(registry, release, &without, default_features, false)
+                    .await;
+                if base.missing.is_empty() {
+                    self.feature_trees.write().await.insert(key, base.clone());
+                }
+                base
+            }
+        };
+        if !tree.missing.is_empty() {
+            // the next hover picks them up
+            let crates = tree
+                .missing
+                .iter()
+                .map(|v| (registry.map(ToOwned::to_owned), v.clone()))
+                .collect();
+            tokio::spawn(self.info.clone().prefetch(crates));
+        }
+        let added = tree
+            .crates
+            .keys()
+            .filter(|v| !base.crates.contains_key(*v))
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let lock = self.get_lock(uri);
+        let not_locked = added
+            .iter()
+            .filter(|v| lock.is_some_and(|lock| lock.graph().find(v).is_empty()))
+            .cloned()
+            .collect();
+        FeatureExpansion {
+            resolved,
+            tree,
+            added,
+            not_locked,
+        }
+    }
+
+    /// document-features docs of a dependency, read from its unpacked source in
+    /// `$CARGO_HOME/registry/src`. Uses the locked version, or the release the
+    /// requirement picks. Kept until the manifest is reloaded or evicted
+    pub async fn dependency_feature_docs(
+        &self,
+        uri: &Uri,
+        dep: &Dependency,
+    ) -> HashMap<String, String> {
+        let version = match self.resolve_dependency(uri, dep) {
+            Some(package) => Some(package.version.to_string()),
+            None => self.dependency_release(uri, dep).await.map(|v| v.vers),
+        };
+        let Some(version) = version else {
+            return HashMap::new();
+        };
+        let name = dep.crate_name();
+        let key = (uri.clone(), name.clone(), version.clone());
+        if let Some(docs) = self.feature_docs.read().await.get(&key) {
+            return docs.clone();
+        }
+        let docs = tokio::task::spawn_blocking(move || published_feature_docs(&name, &version))
+            .await
+            .unwrap_or_default();
+        self.feature_docs.write().await.insert(key, docs.clone());
+        docs
+    }
+
+    /// Directory holding the source of a dependency: the path of path dependencies, the
+    /// unpacked registry source or the git checkout of the locked package. Searching the
+    /// cargo home runs as a blocking task
+    pub async fn dependency_source_dir(&self, uri: &Uri, dep: &Dependency) -> Option<PathBuf> {
+        let source = self.effective_source(uri, dep)?;
+        if let DepSource::Path(path) = source {
+            // inherited paths are relative to the workspace root
+            let base = match &dep.source {
+                DepSource::Workspace(_) => self.get_workspace(uri).unwrap_or(uri),
+                _ => uri,
+            };
+            let dir = base.to_file_path().ok()?.parent()?.join(&path.value.data);
+            return dir.is_dir().then_some(dir);
+        }
+        let package = self.resolve_dependency(uri, dep)?;
+        let name = dep.crate_name();
+        let rev = package.rev().map(ToOwned::to_owned);
+        let version = package.version.to_string();
+        tokio::task::spawn_blocking(move || match rev {
+            Some(rev) => git_checkout(&name, &rev),
+            None => registry_source(&name, &version),
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Effective features of a dependency, see [`Root1::resolve_features`]
+    pub async fn dependency_features(
+        &self,
+        uri: &Uri,
+        dep: &Dependency,
+    ) -> Option<ResolvedFeatures> {
+        let release = self.dependency_release(uri, dep).await?;
+        let (features, default_features) = self.requested_features(uri, dep);
+        Some(release.resolve_features(features.iter().map(String::as_str), default_features))
+    }
+}
+
+/// document-features docs of an unpacked registry release
+fn published_feature_docs(name: &str, version: &str) -> HashMap<String, String> {
+    let Some(dir) = registry_source(name, version) else {
+        return HashMap::new();
+    };
+    // publishing normalizes Cargo.toml and drops the comments, the original is kept
+    read_to_string(dir.join("Cargo.toml.orig"))
+        .or_else(|_| read_to_string(dir.join("Cargo.toml")))
+        .map(|v| feature_docs(&v))
+        .unwrap_or_default()
+}
+
+fn short_list(items: &[&str]) -> String {
+    const LIMIT: usize = 4;
+    let mut out = items
+        .iter()
+        .take(LIMIT)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if items.len() > LIMIT {
+        out.push_str(&format!(" +{}", items.len() - LIMIT));
+    }
+    out
+}
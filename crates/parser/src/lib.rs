@@ -1,7 +1,12 @@
+mod advisory;
 mod analyze;
 pub mod config;
+pub mod dependency_tree;
+pub mod family;
+pub mod features;
 mod format;
 pub mod lock;
+mod lockfile;
 pub mod static_structure;
 pub mod structs;
 pub mod toml;
@@ -9,19 +14,27 @@ pub mod tree;
 mod tree_to_struct;
 
 use std::{
-    collections::HashMap, fs::read_to_string, panic::catch_unwind, path::PathBuf, sync::Arc,
+    collections::{HashMap, HashSet},
+    fs::read_to_string,
+    panic::catch_unwind,
+    path::PathBuf,
+    sync::Arc,
 };
 
 use async_recursion::async_recursion;
 use config::Config;
+use features::{FeatureDocsKey, FeatureTreeKey};
 use glob::Pattern;
-use info_provider::InfoProvider;
+use info_provider::{features::FeatureTree, InfoProvider};
 use lock::LoggedRwLock;
 use ropey::Rope;
 use static_structure::{parse_all, Parsed};
-use structs::lock::{CargoLockRaw, Package};
+use structs::{
+    feat::document_features,
+    lock::{CargoLock, Duplicate, Package, WhyNode},
+};
 use tokio::sync::RwLock;
-use toml::{Dependency, Positioned, Toml};
+use toml::{DepSource, Dependency, Positioned, Toml};
 use tower_lsp::{lsp_types::MessageType, Client};
 use tree::{PathValue, RangeExclusive, Tree};
 use tree_to_struct::to_struct;
@@ -33,12 +46,20 @@ pub struct Db {
     pub client: Client,
     pub static_data: Parsed,
     files: HashMap<Uri, Rope>,
+    /// files the client currently has open, their content is owned by the client
+    open: HashSet<Uri>,
+    /// manifests loaded because a workspace references them
+    loaded: HashSet<Uri>,
     trees: HashMap<Uri, Tree>,
     tomls: HashMap<Uri, Toml>,
     info: Arc<InfoProvider>,
     workspaces: Workspaces,
-    locks: HashMap<Uri, CargoLockRaw>,
+    locks: HashMap<Uri, CargoLock>,
     pub warnings: Arc<RwLock<HashMap<Uri, Vec<Warning>>>>,
+    /// Builds without the hovered feature, see [`Db::expand_feature`]
+    feature_trees: Arc<RwLock<HashMap<FeatureTreeKey, FeatureTree>>>,
+    /// document-features docs of dependencies, see [`Db::dependency_feature_docs`]
+    feature_docs: Arc<RwLock<HashMap<FeatureDocsKey, HashMap<String, String>>>>,
     pub config: Config,
 }
 
@@ -61,6 +82,7 @@ impl Workspaces {
 
 #[derive(Debug, Clone)]
 pub enum Level {
+    Info,
     Warn,
     Error,
 }
@@ -71,6 +93,10 @@ pub struct Warning {
     msg: String,
     start: (usize, usize),
     end: (usize, usize),
+    /// Package to run `cargo update -p` on to fix it
+    update: Option<String>,
+    /// Release that fixes it, the `--precise` of `update` or else a new version requirement
+    precise: Option<String>,
 }
 
 impl Db {
@@ -84,11 +110,15 @@ impl Db {
                 client,
                 info,
                 files: HashMap::new(),
+                open: HashSet::new(),
+                loaded: HashSet::new(),
                 trees: HashMap::new(),
                 tomls: HashMap::new(),
                 workspaces: Default::default(),
                 locks: HashMap::new(),
                 warnings: Default::default(),
+                feature_trees: Default::default(),
+                feature_docs: Default::default(),
             },
         ));
         sel
@@ -114,53 +144,231 @@ impl Db {
         }
     }
 
-    pub fn remove_workspace(&mut self, workspace_uri: &Url) {
-        self.files
-            .retain(|uri, _| !Self::is_within_workspace(uri, workspace_uri));
-        self.trees
-            .retain(|uri, _| !Self::is_within_workspace(uri, workspace_uri));
-        self.tomls
-            .retain(|uri, _| !Self::is_within_workspace(uri, workspace_uri));
+    pub async fn remove_workspace(&mut self, workspace_uri: &Url) {
+        let removed = self
+            .files
+            .keys()
+            .filter(|uri| Self::is_within_workspace(uri, workspace_uri))
+            .cloned()
+            .collect::<Vec<_>>();
+        for uri in removed {
+            self.evict(&uri).await;
+        }
+        self.workspaces
+            .0
+            .retain(|_, root| !Self::is_within_workspace(root, workspace_uri));
+        self.loaded
+            .retain(|uri| !Self::is_within_workspace(uri, workspace_uri));
+        self.prune_locks();
     }
 
-    fn is_within_workspace(file_uri: &Url, workspace_uri: &Url) -> bool {
-        file_uri.as_str().starts_with(workspace_uri.as_str())
+    /// Forgets members that are no longer in the `[workspace] members` of `root`. Open ones
+    /// stay until the client closes them
+    async fn prune_members(&mut self, root: &Uri, members: &[Uri]) {
+        let left = self
+            .workspaces
+            .0
+            .iter()
+            .filter(|(pattern, v)| {
+                *v == root && !members.iter().any(|m| pattern.matches(m.as_str()))
+            })
+            .map(|(pattern, _)| pattern.clone())
+            .collect::<Vec<_>>();
+        for pattern in left {
+            self.workspaces.0.remove(&pattern);
+            let uris = self
+                .loaded
+                .iter()
+                .filter(|v| pattern.matches(v.as_str()))
+                .cloned()
+                .collect::<Vec<_>>();
+            for uri in uris {
+                match self.open.contains(&uri) {
+                    true => {
+                        self.loaded.remove(&uri);
+                    }
+                    false => self.evict(&uri).await,
+                }
+            }
+        }
+        self.prune_locks();
+    }
+
+    /// The client closed the file. Manifests a workspace references fall back to the
+    /// on-disk version, everything else is dropped.
+    pub async fn close(&mut self, uri: &Uri) {
+        self.open.remove(uri);
+        if self.loaded.contains(uri) {
+            self.add_file(uri);
+            self.reload(uri.clone()).await;
+        } else {
+            self.evict(uri).await;
+            self.prune_locks();
+        }
+        if self.locks.contains_key(uri) {
+            // drop unsaved edits of the lock file
+            self.update_lock(uri.clone()).await;
+        }
+    }
+
+    async fn evict(&mut self, uri: &Uri) {
+        self.feature_trees
+            .write()
+            .await
+            .retain(|key, _| &key.0 != uri);
+        self.feature_docs
+            .write()
+            .await
+            .retain(|key, _| &key.0 != uri);
+        self.open.remove(uri);
+        self.loaded.remove(uri);
+        self.files.remove(uri);
+        self.trees.remove(uri);
+        let had_toml = self.tomls.remove(uri).is_some();
+        let had_warnings = self.warnings.write().await.remove(uri).is_some();
+        if had_toml || had_warnings {
+            self.client
+                .publish_diagnostics(uri.clone(), vec![], None)
+                .await;
+        }
+    }
+
+    /// Drops lock files no cached manifest depends on
+    fn prune_locks(&mut self) {
+        let used = self
+            .tomls
+            .keys()
+            .map(|uri| self.lock_uri(uri))
+            .collect::<HashSet<_>>();
+        let open = &self.open;
+        self.locks
+            .retain(|uri, _| used.contains(uri) || open.contains(uri));
+    }
+
+    /// Folder of the workspace root manifest, or of the manifest itself
+    pub fn root_dir(&self, uri: &Uri) -> Option<PathBuf> {
+        let root = self.workspaces.get(uri).unwrap_or(uri);
+        Some(root.to_file_path().ok()?.parent()?.to_path_buf())
     }
 
-    pub fn get_lock(&self, uri: &Uri) -> Option<&CargoLockRaw> {
+    pub fn lock_uri(&self, uri: &Uri) -> Uri {
         let mut file = self.workspaces.get(uri).unwrap_or(uri).clone();
         if let Ok(mut v) = file.path_segments_mut() {
             v.pop();
             v.push("Cargo.lock");
         }
-        self.locks.get(&file)
+        file
     }
 
-    pub async fn hints(&self, uri: &Uri) -> Option<Vec<((usize, usize), Package)>> {
+    fn is_within_workspace(file_uri: &Url, workspace_uri: &Url) -> bool {
+        file_uri.as_str().starts_with(workspace_uri.as_str())
+    }
+
+    pub fn get_lock(&self, uri: &Uri) -> Option<&CargoLock> {
+        self.locks.get(&self.lock_uri(uri))
+    }
+
+    /// The locked package a dependency of the manifest resolved to.
+    /// `workspace = true` dependencies are looked up in the workspace root
+    pub fn resolve_dependency(&self, uri: &Uri, dep: &Dependency) -> Option<Package> {
+        let index = self.resolve_dependency_index(uri, dep)?;
+        self.get_lock(uri)?.package_at(index)
+    }
+
+    /// Index of the resolved package in the lock, see [`Db::resolve_dependency`]
+    pub fn resolve_dependency_index(&self, uri: &Uri, dep: &Dependency) -> Option<usize> {
+        let lock = self.get_lock(uri)?;
         let toml = self.tomls.get(uri)?;
-        let mut root_file = match self.workspaces.get(uri) {
-            None => uri,
-            Some(v) => v,
+        // the dependencies of a workspace root are the shared `[workspace.dependencies]`
+        let member = match toml.workspace {
+            true => None,
+            false => toml.name.as_ref().map(|v| v.data.as_str()),
+        };
+        let registry_index = self
+            .effective_source(uri, dep)
+            .and_then(|v| v.registry())
+            .and_then(|v| self.info.registry_url(v));
+        lock.resolve_index(
+            member,
+            dep,
+            self.inherited(uri, dep),
+            registry_index.as_deref(),
+        )
+    }
+
+    /// The workspace root entry of a `workspace = true` dependency
+    pub fn inherited(&self, uri: &Uri, dep: &Dependency) -> Option<&Dependency> {
+        if !matches!(dep.source, DepSource::Workspace(_)) {
+            return None;
         }
-        .clone();
-        if let Ok(mut v) = root_file.path_segments_mut() {
-            v.pop();
-            v.push("Cargo.lock");
+        self.get_workspace(uri)
+            .and_then(|v| self.tomls.get(v))
+            .and_then(|v| v.dependencies.iter().find(|v| v.data.name() == dep.name()))
+            .map(|v| &v.data)
+    }
+
+    /// Source of a dependency, from the workspace root for `workspace = true`
+    pub fn effective_source<'a>(&'a self, uri: &Uri, dep: &'a Dependency) -> Option<&'a DepSource> {
+        match &dep.source {
+            DepSource::Workspace(_) => Some(&self.inherited(uri, dep)?.source),
+            source => Some(source),
         }
-        let lock = self.locks.get(&root_file)?;
-        let packges = lock.packages();
-        let extract = |id: String| packges.get(&id)?.first();
+    }
+
+    /// Minimum supported Rust version of the package, `rust-version.workspace = true`
+    /// is looked up in the workspace root
+    pub fn msrv(&self, uri: &Uri) -> Option<rust_version::RustVersion> {
+        let toml = self.tomls.get(uri)?;
+        let value = match toml.inherits_rust_version {
+            true => self
+                .tomls
+                .get(self.get_workspace(uri).unwrap_or(uri))?
+                .workspace_rust_version
+                .as_ref()?,
+            // a virtual workspace root goes by `workspace.package`
+            false => toml
+                .rust_version
+                .as_ref()
+                .or(toml.workspace_rust_version.as_ref())?,
+        };
+        rust_version::RustVersion::try_from(value.data.as_str()).ok()
+    }
+
+    pub fn duplicates(&self, uri: &Uri) -> Option<Vec<Duplicate>> {
+        Some(self.get_lock(uri)?.duplicates().to_vec())
+    }
+
+    pub fn why(&self, uri: &Uri, name: &str, version: Option<&str>) -> Option<Vec<WhyNode>> {
+        Some(self.get_lock(uri)?.why(name, version))
+    }
+
+    pub async fn hints(&self, uri: &Uri) -> Option<Vec<((usize, usize), Package)>> {
+        let toml = self.tomls.get(uri)?;
         let data = toml
             .dependencies
             .iter()
             .filter_map(|v| {
-                self.get_offset(uri, v.end as usize)
-                    .map(|pos| (pos, extract(v.data.crate_name())))
+                let pos = self.get_offset(uri, v.end as usize)?;
+                Some((pos, self.resolve_dependency(uri, &v.data)?))
             })
-            .filter_map(|(pos, p)| p.map(|p| (pos, p.clone())))
             .collect::<Vec<_>>();
         Some(data)
     }
+    /// Registry dependencies of all loaded manifests as (registry, crate name)
+    pub fn registry_dependencies(&self) -> Vec<(Option<String>, String)> {
+        self.tomls
+            .values()
+            .flat_map(|toml| &toml.dependencies)
+            .filter_map(|dep| match &dep.data.source {
+                DepSource::Version { registry, .. } => Some((
+                    registry.as_ref().map(|v| v.value.data.clone()),
+                    dep.data.crate_name(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
     pub fn get_content(&self, uri: &Uri) -> Option<String> {
         Some(self.files.get(uri)?.to_string())
     }
@@ -219,18 +427,79 @@ impl Db {
         }
         None
     }
+    /// Parses the lock file again. Open lock files use the editor content
     pub async fn update_lock(&mut self, uri: Uri) {
-        if let Ok(path) = uri.to_file_path() {
-            if let Ok(str) = read_to_string(path) {
-                if let Ok(lock) = ::toml::from_str::<CargoLockRaw>(&str) {
-                    self.locks.insert(uri, lock);
-                }
-            }
+        let content = match self.files.get(&uri) {
+            Some(v) if self.is_open(&uri) => Some(v.to_string()),
+            _ => uri.to_file_path().ok().and_then(|v| read_to_string(v).ok()),
+        };
+        if let Some(lock) = content.and_then(|v| CargoLock::parse(&v).ok()) {
+            self.locks.insert(uri, lock);
         }
 
         self.analyze(None).await;
     }
 
+    pub fn set_open(&mut self, uri: &Uri) {
+        self.open.insert(uri.clone());
+    }
+
+    pub fn is_open(&self, uri: &Uri) -> bool {
+        self.open.contains(uri)
+    }
+
+    pub fn is_tracked(&self, uri: &Uri) -> bool {
+        self.files.contains_key(uri)
+    }
+
+    /// Re-reads a manifest from disk after it was changed outside of the editor.
+    /// Open files are skipped, the client sends their content via did_change. A manifest
+    /// that isn't tracked yet, like a member recreated by a checkout, reloads its workspace
+    /// root so the root picks it up
+    pub async fn reload_from_disk(&mut self, uri: Uri) -> Option<()> {
+        if self.is_open(&uri) {
+            return None;
+        }
+        if !self.is_tracked(&uri) {
+            let root = self.owning_workspace(&uri)?;
+            return self.reload(root).await;
+        }
+        self.add_file(&uri);
+        self.reload(uri).await
+    }
+
+    /// Workspace root of a manifest: the one whose members match it, or else the closest
+    /// tracked workspace manifest in a parent folder
+    fn owning_workspace(&self, uri: &Uri) -> Option<Uri> {
+        if let Some(root) = self.workspaces.get(uri) {
+            return Some(root.clone());
+        }
+        let path = uri.to_file_path().ok()?;
+        path.parent()?.ancestors().skip(1).find_map(|dir| {
+            let root = Url::from_file_path(dir.join("Cargo.toml")).ok()?;
+            self.tomls
+                .get(&root)
+                .is_some_and(|v| v.workspace)
+                .then_some(root)
+        })
+    }
+
+    /// Drops a manifest that was deleted on disk
+    pub async fn remove_file(&mut self, uri: &Uri) {
+        if self.is_open(uri) {
+            return;
+        }
+        self.evict(uri).await;
+        self.prune_locks();
+        self.analyze(None).await;
+    }
+
+    pub async fn remove_lock(&mut self, uri: &Uri) {
+        if self.locks.remove(uri).is_some() {
+            self.analyze(None).await;
+        }
+    }
+
     pub fn get_dependency(
         &self,
         uri: &Uri,
@@ -252,13 +521,24 @@ impl Db {
 
     #[async_recursion]
     pub async fn reload(&mut self, uri: Uri) -> Option<()> {
+        self.feature_trees
+            .write()
+            .await
+            .retain(|key, _| key.0 != uri);
+        self.feature_docs
+            .write()
+            .await
+            .retain(|key, _| key.0 != uri);
         let content = self.files.get(&uri);
         let mut uri_ = Some(uri.clone());
         if let Some(content) = content {
-            self.add_content(uri.clone(), &content.to_string());
+            let content = content.to_string();
+            self.add_content(uri.clone(), &content);
             if let Some(tree) = self.trees.get(&uri) {
                 let empty = Arc::new(Vec::new());
-                let str = to_struct(tree, empty);
+                let mut str = to_struct(tree, empty);
+                document_features(&mut str.features, &content);
+                let mut members = vec![];
                 if str.workspace {
                     for ur in &str.children {
                         let file_path = uri.to_file_path().ok()?;
@@ -273,8 +553,10 @@ impl Db {
                         if v.is_some() {
                             uri_ = None
                         }
+                        members.push(ur);
                     }
                 }
+                self.prune_members(&uri, &members).await;
                 self.tomls.insert(uri.clone(), str);
             }
         }
@@ -301,6 +583,7 @@ impl Db {
     }
 
     pub async fn try_init(&mut self, uri: &Uri) -> Option<()> {
+        self.loaded.insert(uri.clone());
         if !self.files.contains_key(uri) {
             self.add_file(uri);
         }
@@ -0,0 +1,235 @@
+use std::{future::Future, ops::Range, panic::catch_unwind};
+
+use info_provider::{
+    api::CacheItemOut,
+    resolver::{LockedPackage, Upgrade, UpgradeImpact},
+};
+
+use crate::{
+    analyze::to_diagnostics,
+    structs::lock::{CargoLock, LockPackage},
+    Db, Level, Uri,
+};
+
+impl Db {
+    pub fn lock_file(&self, uri: &Uri) -> Option<&CargoLock> {
+        self.locks.get(uri)
+    }
+
+    /// Byte ranges of the `[[package]]` entries followed by the `[[patch.unused]]` ones.
+    /// An entry reaches from its header up to the next table
+    pub fn lock_entry_spans(&self, uri: &Uri) -> Option<Vec<(&LockPackage, Range<usize>)>> {
+        let text = self.files.get(uri)?.to_string();
+        let lock = self.locks.get(uri)?;
+        let spans = lock
+            .entries()
+            .iter()
+            .chain(&lock.unused_patches)
+            .map(|v| {
+                let end = text
+                    .get(v.header_span.end..)
+                    .and_then(|rest| rest.find("\n["))
+                    .map(|i| v.header_span.end + i)
+                    .unwrap_or(text.len());
+                (v, v.header_span.start..end)
+            })
+            .collect();
+        Some(spans)
+    }
+
+    /// Simulates the manifest requiring `requirement` of its dependency `name` against the
+    /// current lock file, see [`info_provider::InfoProvider::simulate_upgrade`]. The inputs
+    /// are copied out of the Db so the simulation, which fetches index data, runs without it
+    pub fn upgrade_preview(
+        &self,
+        uri: &Uri,
+        name: &str,
+        requirement: &str,
+    ) -> Option<impl Future<Output = UpgradeImpact> + Send + 'static> {
+        let lock = self.get_lock(uri)?;
+        let toml = self.tomls.get(uri)?;
+        let member = &toml.name.as_ref()?.data;
+        let dep = toml
+            .dependencies
+            .iter()
+            .find(|v| v.data.crate_name() == name)?;
+        let entries = lock.entries();
+        let member = entries
+            .iter()
+            .position(|v| v.is_member() && &v.name == member)?;
+        let locked = entries
+            .iter()
+            .enumerate()
+            .map(|(i, v)| LockedPackage {
+                name: v.name.clone(),
+                version: v.version.clone(),
+                source: v.source.clone(),
+                dependencies: lock.graph().dependencies(i).to_vec(),
+            })
+            .collect::<Vec<_>>();
+        let registry = self
+            .effective_source(uri, &dep.data)?
+            .registry()
+            .map(ToOwned::to_owned);
+        let (features, default_features) = self.requested_features(uri, &dep.data);
+        let upgrade = Upgrade {
+            member,
+            name: name.to_owned(),
+            requirement: requirement.to_owned(),
+            features,
+            default_features,
+        };
+        let msrv = self.msrv(uri);
+        let info = self.info.clone();
+        Some(async move {
+            info.simulate_upgrade(registry.as_deref(), &locked, &upgrade, msrv.as_ref())
+                .await
+        })
+    }
+
+    /// Index of the `[[package]]` entry the cursor is in
+    pub fn lock_package_at(&self, uri: &Uri, (line, char): (usize, usize)) -> Option<usize> {
+        let file = self.files.get(uri)?;
+        let char = catch_unwind(|| file.line_to_char(line) + char).ok()?;
+        let offset = catch_unwind(|| file.char_to_byte(char)).ok()?;
+        let len = self.locks.get(uri)?.entries().len();
+        self.lock_entry_spans(uri)?
+            .iter()
+            .take(len)
+            .position(|(_, span)| (span.start..=span.end).contains(&offset))
+    }
+
+    /// Diagnostics of an open Cargo.lock: duplicate versions, yanked versions or ones
+    /// missing from the index, checksums that differ from the downloaded archive and
+    /// RustSec advisories
+    pub(crate) async fn analyze_lock(&self, uri: &Uri) -> Option<()> {
+        if !self.files.contains_key(uri) {
+            return None;
+        }
+        let lock = self.locks.get(uri)?;
+        let mut items: Vec<(Level, Range<usize>, String)> = vec![];
+
+        if self.config.duplicate_warnings {
+            let duplicates = lock.duplicates();
+            for package in lock.entries() {
+                let Some(duplicate) = duplicates.iter().find(|v| v.name == package.name) else {
+                    continue;
+                };
+                let others = duplicate
+                    .versions
+                    .iter()
+                    .filter(|v| v.version != package.version)
+                    .map(|v| v.version.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                items.push((
+                    Level::Info,
+                    package.version_span.clone(),
+                    format!("{} is also locked at {others}", package.name),
+                ));
+            }
+        }
+
+        let mut fetch = vec![];
+        let mut checksums = vec![];
+        for package in lock.entries().iter().filter(|v| v.is_crates_io()) {
+            match self.info.get_info_cache(None, &package.name).await {
+                CacheItemOut::Ready(versions) => {
+                    let msg = match versions.iter().find(|v| v.vers == package.version) {
+                        Some(v) if v.yanked => Some("is yanked"),
+                        Some(_) => None,
+                        None => Some("is missing from the index"),
+                    };
+                    if let Some(msg) = msg {
+                        items.push((
+                            Level::Warn,
+                            package.version_span.clone(),
+                            format!("{} {} {msg}", package.name, package.version),
+                        ));
+                    }
+                }
+                CacheItemOut::NotStarted => fetch.push((None, package.name.clone())),
+                CacheItemOut::Pending | CacheItemOut::Error(_) => {}
+            }
+
+            if let (Some(expected), Some(span)) = (&package.checksum, &package.checksum_span) {
+                checksums.push((
+                    package.name.clone(),
+                    package.version.clone(),
+                    expected.clone(),
+                    span.clone(),
+                ));
+            }
+        }
+
+        let actual = self
+            .info
+            .crate_checksums(
+                checksums
+                    .iter()
+                    .map(|v| (v.0.clone(), v.1.clone()))
+                    .collect(),
+            )
+            .await;
+        for ((_, _, expected, span), actual) in checksums.into_iter().zip(actual) {
+            let Some(actual) = actual.filter(|v| v != &expected) else {
+                continue;
+            };
+            items.push((
+                Level::Error,
+                span,
+                format!("Checksum does not match the downloaded crate ({actual})"),
+            ));
+        }
+
+        if !fetch.is_empty() {
+            if let Some(sel) = self.sel.clone() {
+                let info = self.info.clone();
+                let uri = uri.clone();
+                tokio::spawn(async move {
+                    let names = fetch.iter().map(|v| v.1.clone()).collect::<Vec<_>>();
+                    info.clone().prefetch(fetch).await;
+                    // only rerun if something arrived, offline lookups stay NotStarted
+                    for name in names {
+                        if !matches!(
+                            info.get_info_cache(None, &name).await,
+                            CacheItemOut::NotStarted
+                        ) {
+                            let lock = sel.read("analyze lock spawn").await;
+                            lock.analyze_single(&uri).await;
+                            return;
+                        }
+                    }
+                });
+            }
+        }
+
+        let mut advisories = vec![];
+        if self.config.advisory_warnings {
+            for package in lock.entries().iter().filter(|v| v.is_crates_io()) {
+                for (level, msg, fix) in
+                    self.advisories(None, &package.name, &package.version).await
+                {
+                    let update = format!("{}@{}", package.name, package.version);
+                    advisories.push((level, package.version_span.clone(), msg, update, fix));
+                }
+            }
+        }
+
+        let mut warn = vec![];
+        for (level, range, msg, update, precise) in advisories {
+            self.push_warning(&mut warn, uri, range, level, msg, (Some(update), precise));
+        }
+        for (level, range, msg) in items {
+            self.push_warning(&mut warn, uri, range, level, msg, (None, None));
+        }
+        self.warnings
+            .write()
+            .await
+            .insert(uri.clone(), warn.clone());
+        self.client
+            .publish_diagnostics(uri.clone(), to_diagnostics(true, warn), None)
+            .await;
+        Some(())
+    }
+}
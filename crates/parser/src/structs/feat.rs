@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use crate::{
     toml::{Feature, FeatureArgKind, Positioned},
-    tree::Value,
+    tree::{Tree, Value},
 };
 
 pub fn get_features(value: &Value) -> Option<Vec<Positioned<Feature>>> {
@@ -25,16 +27,121 @@ pub fn get_features(value: &Value) -> Option<Vec<Positioned<Feature>>> {
                 out.push(Positioned {
                     start: range.start,
                     end: range.end.max(max),
-                    data: Feature { name, args },
+                    data: Feature {
+                        name,
+                        args,
+                        docs: None,
+                    },
                 });
             }
             Value::NoContent => out.push(Positioned {
                 start: range.start,
                 end: range.end,
-                data: Feature { name, args: vec![] },
+                data: Feature {
+                    name,
+                    args: vec![],
+                    docs: None,
+                },
             }),
             _ => continue,
         };
     }
     Some(out)
 }
+
+/// Fills in `docs` from the `## ` lines directly above each feature, like the
+/// document-features crate does. `#!` lines are section headers and end a doc block
+pub fn document_features(features: &mut [Positioned<Feature>], text: &str) {
+    for feature in features {
+        let start = (feature.data.name.start as usize).min(text.len());
+        let Some(before) = text.get(..start) else {
+            continue;
+        };
+        let before = before.rsplit_once('\n').map(|v| v.0).unwrap_or_default();
+        let mut lines = before
+            .lines()
+            .rev()
+            .map(str::trim)
+            .take_while(|v| v.starts_with("##"))
+            .map(|v| {
+                let v = &v[2..];
+                v.strip_prefix(' ').unwrap_or(v)
+            })
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            continue;
+        }
+        lines.reverse();
+        feature.data.docs = Some(lines.join("\n"));
+    }
+}
+
+/// Feature docs of a manifest that isn't loaded, e.g. the one of a dependency
+pub fn feature_docs(text: &str) -> HashMap<String, String> {
+    let dom = taplo::parser::parse(text).into_dom();
+    let Some(tree) = dom.as_table().map(Tree::from) else {
+        return HashMap::new();
+    };
+    let Some(mut features) = tree
+        .nodes
+        .iter()
+        .find(|v| v.key.value == "features")
+        .and_then(|v| get_features(&v.value))
+    else {
+        return HashMap::new();
+    };
+    document_features(&mut features, text);
+    features
+        .into_iter()
+        .filter_map(|v| Some((v.data.name.data, v.data.docs?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::document_features;
+    use crate::toml::{Feature, Positioned};
+
+    #[test]
+    fn docs() {
+        let text = r#"[features]
+## Enables the standard library
+## and allocations
+std = []
+#! ### Runtimes
+rt = ["std"]
+
+## Macros
+macros = []
+"#;
+        let mut features = ["std", "rt", "macros"]
+            .into_iter()
+            .map(|name| {
+                let start = text.find(&format!("\n{name} =")).unwrap() as u32 + 1;
+                let end = start + name.len() as u32;
+                Positioned::new(
+                    start,
+                    end,
+                    Feature {
+                        name: Positioned::new(start, end, name.to_owned()),
+                        args: vec![],
+                        docs: None,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        document_features(&mut features, text);
+        let docs = features
+            .iter()
+            .map(|v| v.data.docs.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            docs,
+            [
+                Some("Enables the standard library\nand allocations"),
+                None,
+                Some("Macros")
+            ]
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use super::lock::LockPackage;
+
+/// Index over the packages of a Cargo.lock, rebuilt whenever the lock is parsed
+#[derive(Debug, Default)]
+pub struct LockGraph {
+    by_name: HashMap<String, Vec<usize>>,
+    dependencies: Vec<Vec<usize>>,
+    dependents: Vec<Vec<usize>>,
+}
+
+impl LockGraph {
+    pub(crate) fn new(packages: &[LockPackage]) -> Self {
+        let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, package) in packages.iter().enumerate() {
+            by_name.entry(package.name.clone()).or_default().push(i);
+        }
+        let dependencies = packages
+            .iter()
+            .map(|package| {
+                let mut out = package
+                    .dependencies
+                    .iter()
+                    .flat_map(|id| {
+                        by_name
+                            .get(&id.name)
+                            .into_iter()
+                            .flatten()
+                            .copied()
+                            .filter(move |i| id.matches(&packages[*i]))
+                    })
+                    .collect::<Vec<_>>();
+                out.dedup();
+                out
+            })
+            .collect::<Vec<_>>();
+        let mut dependents = vec![vec![]; packages.len()];
+        for (from, to) in dependencies.iter().enumerate() {
+            for to in to {
+                dependents[*to].push(from);
+            }
+        }
+        Self {
+            by_name,
+            dependencies,
+            dependents,
+        }
+    }
+
+    /// Indices of all locked versions of a package
+    pub fn find(&self, name: &str) -> &[usize] {
+        self.by_name
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = (&String, &Vec<usize>)> {
+        self.by_name.iter()
+    }
+
+    pub fn dependencies(&self, index: usize) -> &[usize] {
+        self.dependencies
+            .get(index)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn dependents(&self, index: usize) -> &[usize] {
+        self.dependents
+            .get(index)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
@@ -1,41 +1,530 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fmt::Display,
+    ops::Range,
+};
 
-use serde::Deserialize;
-use serde_json::Value;
+use ::toml::Spanned;
+use rust_version::VersionReq;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
-use super::version::RustVersion;
+use super::{graph::LockGraph, version::RustVersion};
+use crate::toml::{DepSource, Dependency};
 
 #[derive(Debug, Deserialize)]
+struct CargoLockRaw {
+    version: Option<u32>,
+    #[serde(default)]
+    package: Vec<Spanned<PackageRaw>>,
+    /// v1 lock files keep the checksums here
+    #[serde(default)]
+    metadata: HashMap<String, ::toml::Value>,
+    #[serde(default)]
+    patch: PatchRaw,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PatchRaw {
+    #[serde(default)]
+    unused: Vec<Spanned<PackageRaw>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageRaw {
+    name: Spanned<String>,
+    version: Spanned<String>,
+    source: Option<Spanned<String>>,
+    checksum: Option<Spanned<String>>,
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
 /// parsed information of the Cargo.lock file
-pub struct CargoLockRaw {
-    package: Vec<PackageRaw>,
+#[derive(Debug, Default)]
+pub struct CargoLock {
+    /// Format version, 1 to 4. v1 and v2 files don't write it and are told apart by
+    /// the `[metadata]` checksums and the dependency format
+    pub version: u32,
+    packages: Vec<LockPackage>,
+    /// `[[patch.unused]]`, patches that didn't match any dependency
+    pub unused_patches: Vec<LockPackage>,
+    graph: LockGraph,
+    /// Computed once on parse, see [`CargoLock::duplicates`]
+    duplicates: Vec<Duplicate>,
+}
+
+/// A `[[package]]` entry
+#[derive(Debug, Clone)]
+pub struct LockPackage {
+    pub name: String,
+    pub version: String,
+    pub source: Option<String>,
+    /// Taken from the `[metadata]` table in v1 files
+    pub checksum: Option<String>,
+    pub dependencies: Vec<DependencyId>,
+    /// Byte range of the `[[package]]` header
+    pub header_span: Range<usize>,
+    pub name_span: Range<usize>,
+    pub version_span: Range<usize>,
+    pub source_span: Option<Range<usize>>,
+    /// None for v1 files, their checksums live in `[metadata]`
+    pub checksum_span: Option<Range<usize>>,
+}
+
+const CRATES_IO: [&str; 2] = [
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
+impl LockPackage {
+    fn new(raw: Spanned<PackageRaw>) -> Self {
+        let header_span = raw.span();
+        let raw = raw.into_inner();
+        Self {
+            name_span: raw.name.span(),
+            version_span: raw.version.span(),
+            source_span: raw.source.as_ref().map(Spanned::span),
+            checksum_span: raw.checksum.as_ref().map(Spanned::span),
+            name: raw.name.into_inner(),
+            version: raw.version.into_inner(),
+            source: raw.source.map(Spanned::into_inner),
+            checksum: raw.checksum.map(Spanned::into_inner),
+            dependencies: raw
+                .dependencies
+                .iter()
+                .map(|v| DependencyId::from(v.as_str()))
+                .collect(),
+            header_span,
+        }
+    }
+
+    /// Path dependencies, including workspace members, have no source
+    pub fn is_member(&self) -> bool {
+        self.source.is_none()
+    }
+
+    pub fn is_crates_io(&self) -> bool {
+        self.source
+            .as_deref()
+            .is_some_and(|v| CRATES_IO.contains(&v))
+    }
+
+    /// Locked from the registry with the index url, which cargo configs write with or
+    /// without the `sparse+` prefix
+    pub fn is_from_index(&self, index: &str) -> bool {
+        let index = index.strip_prefix("sparse+").unwrap_or(index);
+        self.source
+            .as_deref()
+            .and_then(|v| v.strip_prefix("sparse+").or(v.strip_prefix("registry+")))
+            .is_some_and(|v| v.trim_end_matches('/') == index.trim_end_matches('/'))
+    }
+
+    /// None if the version can't be parsed
+    pub fn to_package(&self) -> Option<Package> {
+        Some(Package {
+            version: RustVersion::try_from(self.version.as_str()).ok()?,
+            source: self.source.as_deref().map(Source::from),
+            checksum: self.checksum.clone(),
+            dependencies: self.dependencies.clone(),
+        })
+    }
+
+    fn id(&self) -> DependencyId {
+        DependencyId {
+            name: self.name.clone(),
+            version: Some(self.version.clone()),
+            source: self.source.clone(),
+        }
+    }
+}
+
+/// Entry of a `dependencies` list. It is `name`, `name version` or
+/// `name version (source)`, depending on what is needed to make it unique.
+/// v1 files always write all three
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyId {
+    pub name: String,
+    pub version: Option<String>,
+    pub source: Option<String>,
+}
+
+impl DependencyId {
+    pub fn matches(&self, package: &LockPackage) -> bool {
+        self.name == package.name
+            && self.version.as_ref().is_none_or(|v| *v == package.version)
+            && self
+                .source
+                .as_ref()
+                .is_none_or(|v| Some(v) == package.source.as_ref())
+    }
+}
+
+impl From<&str> for DependencyId {
+    fn from(value: &str) -> Self {
+        let mut parts = value.splitn(3, ' ');
+        Self {
+            name: parts.next().unwrap_or_default().to_owned(),
+            version: parts.next().map(ToOwned::to_owned),
+            source: parts
+                .next()
+                .map(|v| v.trim_start_matches('(').trim_end_matches(')').to_owned()),
+        }
+    }
+}
+
+impl Display for DependencyId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, " {version}")?;
+        }
+        if let Some(source) = &self.source {
+            write!(f, " ({source})")?;
+        }
+        Ok(())
+    }
 }
 
-impl CargoLockRaw {
+impl CargoLock {
+    /// Parses the lock file and indexes its dependency graph
+    pub fn parse(content: &str) -> Result<Self, ::toml::de::Error> {
+        let raw = ::toml::from_str::<CargoLockRaw>(content)?;
+        let mut packages = raw
+            .package
+            .into_iter()
+            .map(LockPackage::new)
+            .collect::<Vec<_>>();
+        let v1_checksums = raw
+            .metadata
+            .iter()
+            .filter_map(|(k, v)| Some((k.strip_prefix("checksum ")?, v.as_str()?)))
+            .collect::<HashMap<_, _>>();
+        for package in &mut packages {
+            if package.checksum.is_none() {
+                package.checksum = v1_checksums
+                    .get(package.id().to_string().as_str())
+                    .filter(|v| **v != "<none>")
+                    .map(|v| v.to_string());
+            }
+        }
+        let version = raw.version.unwrap_or_else(|| {
+            let full_ids = packages
+                .iter()
+                .flat_map(|v| &v.dependencies)
+                .any(|v| v.version.is_some() && v.source.is_some());
+            match !v1_checksums.is_empty() || full_ids {
+                true => 1,
+                false => 2,
+            }
+        });
+        let graph = LockGraph::new(&packages);
+        Ok(Self {
+            version,
+            duplicates: find_duplicates(&packages, &graph),
+            graph,
+            packages,
+            unused_patches: raw.patch.unused.into_iter().map(LockPackage::new).collect(),
+        })
+    }
+
+    pub fn graph(&self) -> &LockGraph {
+        &self.graph
+    }
+
+    pub fn entries(&self) -> &[LockPackage] {
+        &self.packages
+    }
+
+    pub fn entry(&self, index: usize) -> Option<&LockPackage> {
+        self.packages.get(index)
+    }
+
+    fn is_member(&self, index: usize) -> bool {
+        self.packages[index].is_member()
+    }
+
     /// converts the raw data into a more usable format
     pub fn packages(&self) -> HashMap<String, Vec<Package>> {
-        let mut out = HashMap::new();
-        for package in &self.package {
-            let by_name: &mut Vec<Package> = out.entry(package.name.clone()).or_default();
-            by_name.push(Package {
-                version: RustVersion::try_from(package.version.as_str()).unwrap(),
-                source: package.source.as_ref().map(|v| v.as_str().into()),
-                checksum: package.checksum.clone(),
-                dependencies: package.dependencies.clone(),
+        let mut out: HashMap<String, Vec<Package>> = HashMap::new();
+        for package in &self.packages {
+            if let Some(v) = package.to_package() {
+                out.entry(package.name.clone()).or_default().push(v);
+            }
+        }
+        out
+    }
+
+    /// Finds the locked package a dependency of the manifest `member` resolved to.
+    /// Candidates are narrowed down by the dependency list of the member's own entry,
+    /// the kind of source and the version requirement; the highest remaining one wins.
+    /// `inherited` is the workspace entry of a `workspace = true` dependency, its source
+    /// and requirement apply. `registry_index` is the index url of the alternate registry
+    /// the dependency names, when it is configured
+    pub fn resolve(
+        &self,
+        member: Option<&str>,
+        dep: &Dependency,
+        inherited: Option<&Dependency>,
+        registry_index: Option<&str>,
+    ) -> Option<Package> {
+        self.resolve_index(member, dep, inherited, registry_index)
+            .and_then(|i| self.packages[i].to_package())
+    }
+
+    /// Index of the resolved package, see [`CargoLock::resolve`]
+    pub fn resolve_index(
+        &self,
+        member: Option<&str>,
+        dep: &Dependency,
+        inherited: Option<&Dependency>,
+        registry_index: Option<&str>,
+    ) -> Option<usize> {
+        let dep = match &dep.source {
+            DepSource::Workspace(_) => inherited.unwrap_or(dep),
+            _ => dep,
+        };
+        let name = dep.crate_name();
+        let mut candidates = self
+            .graph
+            .find(&name)
+            .iter()
+            .map(|i| (*i, &self.packages[*i]))
+            .collect::<Vec<_>>();
+
+        if let Some(member) = member {
+            let ids = self
+                .packages
+                .iter()
+                .filter(|v| v.name == member && v.is_member())
+                .flat_map(|v| &v.dependencies)
+                .collect::<Vec<_>>();
+            narrow(&mut candidates, |package| {
+                ids.iter().any(|id| id.matches(package))
             });
         }
+        narrow(&mut candidates, |package| {
+            let source = package.source.as_deref().unwrap_or_default();
+            match &dep.source {
+                // crates.io is named in the lock file, other registries by their index url
+                DepSource::Version { registry: None, .. } => package.is_crates_io(),
+                DepSource::Version { .. } => match registry_index {
+                    Some(index) => package.is_from_index(index),
+                    None => {
+                        (source.starts_with("registry+") || source.starts_with("sparse+"))
+                            && !package.is_crates_io()
+                    }
+                },
+                DepSource::Git { .. } => source.starts_with("git+"),
+                DepSource::Path(_) => package.is_member(),
+                _ => true,
+            }
+        });
+        if let DepSource::Version { value, .. } = &dep.source {
+            if let Ok(req) = VersionReq::try_from(value.value.data.as_str()) {
+                narrow(&mut candidates, |package| {
+                    rust_version::RustVersion::try_from(package.version.as_str())
+                        .is_ok_and(|v| req.matches(&v))
+                });
+            }
+        }
+        candidates
+            .into_iter()
+            .max_by_key(|(_, v)| rust_version::RustVersion::try_from(v.version.as_str()).ok())
+            .map(|(i, _)| i)
+    }
+
+    pub fn package_at(&self, index: usize) -> Option<Package> {
+        self.packages.get(index)?.to_package()
+    }
+}
+
+impl CargoLock {
+    /// Whether the lock entry of the workspace member lists `name` as a dependency.
+    /// None if the member isn't locked at all
+    pub fn depends_on(&self, member: &str, name: &str) -> Option<bool> {
+        let mut entries = self
+            .packages
+            .iter()
+            .filter(|v| v.name == member && v.is_member())
+            .peekable();
+        entries.peek()?;
+        Some(
+            entries
+                .flat_map(|v| &v.dependencies)
+                .any(|v| v.name == name),
+        )
+    }
+}
+
+/// A package that is locked at more than one version
+#[derive(Debug, Clone, Serialize)]
+pub struct Duplicate {
+    pub name: String,
+    pub versions: Vec<DuplicateVersion>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateVersion {
+    pub version: String,
+    pub source: Option<String>,
+    /// Direct dependencies of workspace members that pull this copy in
+    pub pulled_in_by: Vec<DirectDependency>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DirectDependency {
+    /// Name of the workspace member
+    pub member: String,
+    /// Package name of the dependency, not the renamed key
+    pub dependency: String,
+}
+
+impl CargoLock {
+    /// Packages locked at more than one version and the direct dependencies of the
+    /// workspace members that pull each copy in
+    pub fn duplicates(&self) -> &[Duplicate] {
+        &self.duplicates
+    }
+}
+
+fn find_duplicates(packages: &[LockPackage], graph: &LockGraph) -> Vec<Duplicate> {
+    let duplicated = graph
+        .names()
+        .filter(|(_, v)| v.len() > 1)
+        .flat_map(|(_, v)| v)
+        .copied()
+        .collect::<HashSet<_>>();
+    let mut pulled_in_by: HashMap<usize, BTreeSet<DirectDependency>> = HashMap::new();
+    let members = (0..packages.len()).filter(|i| packages[*i].is_member());
+    for member in members {
+        for &direct in graph.dependencies(member) {
+            let mut visited = HashSet::from([direct]);
+            let mut queue = VecDeque::from([direct]);
+            while let Some(current) = queue.pop_front() {
+                if duplicated.contains(&current) {
+                    pulled_in_by
+                        .entry(current)
+                        .or_default()
+                        .insert(DirectDependency {
+                            member: packages[member].name.clone(),
+                            dependency: packages[direct].name.clone(),
+                        });
+                }
+                for &next in graph.dependencies(current) {
+                    if visited.insert(next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+    }
+    let mut out = graph
+        .names()
+        .filter(|(_, v)| v.len() > 1)
+        .map(|(name, indices)| {
+            let mut versions = indices
+                .iter()
+                .map(|i| DuplicateVersion {
+                    version: packages[*i].version.clone(),
+                    source: packages[*i].source.clone(),
+                    pulled_in_by: pulled_in_by
+                        .remove(i)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                })
+                .collect::<Vec<_>>();
+            versions.sort_by_key(|v| rust_version::RustVersion::try_from(v.version.as_str()).ok());
+            Duplicate {
+                name: name.to_owned(),
+                versions,
+            }
+        })
+        .collect::<Vec<_>>();
+    out.sort_by(|a, b| a.name.cmp(&b.name));
+    out
+}
+
+/// Inverted dependency tree of a locked package, like `cargo tree -i`
+#[derive(Debug, Clone, Serialize)]
+pub struct WhyNode {
+    pub name: String,
+    pub version: String,
+    /// Workspace members are the roots of the graph
+    pub member: bool,
+    /// Already expanded elsewhere in the tree
+    pub repeated: bool,
+    pub dependents: Vec<WhyNode>,
+}
+
+impl WhyNode {
+    /// Paths from workspace members down to the package, at most `limit`
+    pub fn paths(&self, limit: usize) -> Vec<Vec<String>> {
+        let label = format!("{} {}", self.name, self.version);
+        if self.dependents.is_empty() {
+            // like cargo tree, (*) marks a package whose dependents are listed elsewhere
+            return match self.repeated {
+                true => vec![vec![format!("{label} (*)")]],
+                false => vec![vec![label]],
+            };
+        }
+        let mut out = vec![];
+        for dependent in &self.dependents {
+            for mut path in dependent.paths(limit - out.len()) {
+                path.push(label.clone());
+                out.push(path);
+            }
+            if out.len() >= limit {
+                break;
+            }
+        }
         out
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct PackageRaw {
-    name: String,
-    version: String,
-    source: Option<String>,
-    checksum: Option<String>,
-    dependencies: Option<Vec<Value>>,
+impl CargoLock {
+    /// Why a package is in the lock file, one tree per matching locked version
+    pub fn why(&self, name: &str, version: Option<&str>) -> Vec<WhyNode> {
+        let mut seen = HashSet::new();
+        self.graph
+            .find(name)
+            .iter()
+            .filter(|i| version.is_none_or(|v| self.packages[**i].version == v))
+            .map(|i| self.why_node(*i, &mut seen, &mut vec![]))
+            .collect()
+    }
+
+    fn why_node(&self, index: usize, seen: &mut HashSet<usize>, stack: &mut Vec<usize>) -> WhyNode {
+        let package = &self.packages[index];
+        let mut node = WhyNode {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            member: self.is_member(index),
+            repeated: false,
+            dependents: vec![],
+        };
+        if !seen.insert(index) {
+            node.repeated = !self.graph.dependents(index).is_empty();
+            return node;
+        }
+        stack.push(index);
+        for &dependent in self.graph.dependents(index) {
+            // dev-dependencies can form cycles
+            if !stack.contains(&dependent) {
+                node.dependents.push(self.why_node(dependent, seen, stack));
+            }
+        }
+        stack.pop();
+        node
+    }
+}
+
+/// Keeps the candidates matching `f`, unless none of them does
+fn narrow(candidates: &mut Vec<(usize, &LockPackage)>, f: impl Fn(&LockPackage) -> bool) {
+    if candidates.iter().any(|(_, v)| f(v)) {
+        candidates.retain(|(_, v)| f(v));
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -48,7 +537,7 @@ pub struct Package {
     /// checksum of the package
     checksum: Option<String>,
     /// dependencies of the package
-    dependencies: Option<Vec<Value>>,
+    dependencies: Vec<DependencyId>,
 }
 
 impl Package {
@@ -92,7 +581,9 @@ impl Package {
     }
     pub fn label(&self) -> String {
         match &self.source {
-            Some(Source::Git { rev, .. }) => format!("{} #{}", self.version, &rev[..7]),
+            Some(Source::Git { rev, .. }) => {
+                format!("{} #{}", self.version, rev.get(..7).unwrap_or(rev))
+            }
             _ => self.version.to_string(),
         }
     }
@@ -112,8 +603,10 @@ pub enum Source {
 
 impl From<&str> for Source {
     fn from(value: &str) -> Self {
-        if value.starts_with("registry+") {
-            Source::Registry(value.replace("registry+", ""))
+        if let Some(url) = value.strip_prefix("registry+") {
+            Source::Registry(url.to_owned())
+        } else if value.starts_with("sparse+") {
+            Source::Registry(value.to_owned())
         } else if let Some(url_str) = value.strip_prefix("git+") {
             match Url::parse(url_str) {
                 Ok(url) => {
@@ -143,3 +636,122 @@ impl From<&str> for Source {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::CargoLock;
+
+    const LOCK: &str = r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["old", "syn 2.0.50"]
+
+[[package]]
+name = "old"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = ["syn 1.0.109"]
+
+[[package]]
+name = "syn"
+version = "1.0.109"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "syn"
+version = "2.0.50"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+    #[test]
+    fn duplicates() {
+        let lock = CargoLock::parse(LOCK).unwrap();
+        let duplicates = lock.duplicates();
+        assert_eq!(duplicates.len(), 1);
+        let syn = &duplicates[0];
+        assert_eq!(syn.name, "syn");
+        assert_eq!(syn.versions[0].version, "1.0.109");
+        assert_eq!(syn.versions[0].pulled_in_by[0].dependency, "old");
+        assert_eq!(syn.versions[1].pulled_in_by[0].dependency, "syn");
+        assert_eq!(lock.depends_on("app", "old"), Some(true));
+        assert_eq!(lock.depends_on("app", "serde"), Some(false));
+    }
+
+    #[test]
+    fn formats() {
+        let v1 = r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "serde 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[metadata]
+"checksum serde 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)" = "abc"
+"#;
+        let lock = CargoLock::parse(v1).unwrap();
+        assert_eq!(lock.version, 1);
+        assert_eq!(lock.entries()[1].checksum.as_deref(), Some("abc"));
+        assert_eq!(lock.graph().dependencies(0), &[1]);
+
+        let v4 = r#"
+version = 4
+
+[[package]]
+name = "odd"
+version = "not-a-version"
+
+[[patch.unused]]
+name = "unused"
+version = "0.1.0"
+"#;
+        let lock = CargoLock::parse(v4).unwrap();
+        assert_eq!(lock.version, 4);
+        assert_eq!(lock.unused_patches[0].name, "unused");
+        assert!(lock.packages().is_empty());
+        assert_eq!(CargoLock::parse(LOCK).unwrap().version, 2);
+    }
+
+    #[test]
+    fn registries() {
+        let lock = CargoLock::parse(
+            r#"
+[[package]]
+name = "shared"
+version = "1.0.0"
+source = "sparse+https://one.example.com/index/"
+
+[[package]]
+name = "shared"
+version = "1.0.0"
+source = "registry+https://git.example.com/index"
+"#,
+        )
+        .unwrap();
+        let [one, git] = lock.entries() else {
+            panic!("expected two packages");
+        };
+        assert!(one.is_from_index("sparse+https://one.example.com/index"));
+        assert!(one.is_from_index("https://one.example.com/index/"));
+        assert!(!one.is_from_index("https://git.example.com/index"));
+        assert!(git.is_from_index("https://git.example.com/index"));
+        assert!(!git.is_crates_io());
+    }
+
+    #[test]
+    fn why() {
+        let lock = CargoLock::parse(LOCK).unwrap();
+        let trees = lock.why("syn", Some("1.0.109"));
+        assert_eq!(
+            trees[0].paths(5),
+            vec![vec!["app 0.1.0", "old 1.0.0", "syn 1.0.109"]]
+        );
+    }
+}
@@ -1,4 +1,5 @@
 pub mod deps;
 pub mod feat;
+pub mod graph;
 pub mod lock;
 pub mod version;
@@ -1,11 +1,20 @@
 use std::{fmt::Display, sync::Arc};
 
+use serde::Serialize;
 use url::Url;
 
 use crate::tree::RangeExclusive;
 
 #[derive(Debug)]
 pub struct Toml {
+    /// Name of the `[package]`
+    pub name: Option<Positioned<String>>,
+    /// `package.rust-version`, None if missing or inherited
+    pub rust_version: Option<Positioned<String>>,
+    /// `package.rust-version.workspace = true`
+    pub inherits_rust_version: bool,
+    /// `workspace.package.rust-version`
+    pub workspace_rust_version: Option<Positioned<String>>,
     pub workspace: bool,
     pub children: Vec<String>,
     pub dependencies: Vec<Positioned<Dependency>>,
@@ -15,6 +24,10 @@ pub struct Toml {
 impl Toml {
     pub fn join(self, other: Self) -> Self {
         Self {
+            name: self.name.or(other.name),
+            rust_version: self.rust_version.or(other.rust_version),
+            inherits_rust_version: self.inherits_rust_version || other.inherits_rust_version,
+            workspace_rust_version: self.workspace_rust_version.or(other.workspace_rust_version),
             workspace: self.workspace || other.workspace,
             children: self.children.into_iter().chain(other.children).collect(),
             dependencies: self
@@ -52,7 +65,8 @@ impl<T> Positioned<T> {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DependencyKind {
     /// dependencies
     Normal,
@@ -479,6 +493,8 @@ pub struct Feature {
     pub name: Positioned<String>,
     /// = [...]
     pub args: Vec<FeatureArgKind>,
+    /// `## ` comment lines above the entry, the document-features convention
+    pub docs: Option<String>,
 }
 
 #[derive(Debug)]
@@ -521,6 +537,19 @@ impl From<Positioned<String>> for FeatureArgKind {
     }
 }
 
+impl Display for FeatureArgKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FeatureArgKind::CrateFeature(v) => write!(f, "{}", v.data),
+            FeatureArgKind::DependencyFeature {
+                dependency,
+                feature,
+            } => write!(f, "{}/{}", dependency.data, feature.data),
+            FeatureArgKind::Dependency(v) => write!(f, "dep:{}", v.data),
+        }
+    }
+}
+
 impl FeatureArgKind {
     pub fn range(&self) -> RangeExclusive {
         match self {
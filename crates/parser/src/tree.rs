@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{fmt::Display, ops};
 
 use taplo::{
     dom::{
@@ -208,6 +208,12 @@ impl<T> From<&Positioned<T>> for RangeExclusive {
     }
 }
 
+impl From<RangeExclusive> for ops::Range<usize> {
+    fn from(value: RangeExclusive) -> Self {
+        value.start as usize..value.end as usize
+    }
+}
+
 impl RangeExclusive {
     pub fn new(start: u32, end: u32) -> Self {
         Self { start, end }
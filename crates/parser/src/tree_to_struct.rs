@@ -12,10 +12,29 @@ pub fn to_struct(tree: &Tree, target: Arc<Vec<Positioned<Target>>>) -> Toml {
     let mut features = vec![];
     let mut tar = vec![];
     let mut mem = vec![];
+    let mut name = None;
+    let mut rust_version = None;
+    let mut inherits_rust_version = false;
+    let mut workspace_rust_version = None;
     for value in tree.nodes.iter() {
         match value.key.value.as_str() {
             "profile" | "badges" | "lints" | "patch" | "replace" | "bench" | "test" | "example"
-            | "package" | "lib" | "bin" => { /* ignore */ }
+            | "lib" | "bin" => { /* ignore */ }
+            "package" => {
+                name = value
+                    .value
+                    .as_tree()
+                    .and_then(|v| v.nodes.iter().find(|v| v.key.value == "name"))
+                    .and_then(|v| v.value.as_str());
+                if let Some(value) = get_rust_version(&value.value) {
+                    rust_version = value.as_str();
+                    inherits_rust_version = value
+                        .as_tree()
+                        .and_then(|v| v.nodes.iter().find(|v| v.key.value == "workspace"))
+                        .and_then(|v| v.value.as_bool())
+                        .is_some_and(|v| v.data);
+                }
+            }
             "dependencies" => {
                 let deps = get_dependencies(&value.value, DependencyKind::Normal, target.clone())
                     .unwrap_or_default();
@@ -66,6 +85,12 @@ pub fn to_struct(tree: &Tree, target: Arc<Vec<Positioned<Target>>>) -> Toml {
             }
             "workspace" => {
                 mem.push(generate_workspace(&value.value, target.clone()));
+                workspace_rust_version = value
+                    .value
+                    .as_tree()
+                    .and_then(|v| v.nodes.iter().find(|v| v.key.value == "package"))
+                    .and_then(|v| get_rust_version(&v.value))
+                    .and_then(|v| v.as_str());
             }
             _ => {}
         }
@@ -81,6 +106,10 @@ pub fn to_struct(tree: &Tree, target: Arc<Vec<Positioned<Target>>>) -> Toml {
     );
     let tar = tar.into_iter().reduce(|acc, i| acc.join(i));
     let r = Toml {
+        name,
+        rust_version,
+        inherits_rust_version,
+        workspace_rust_version,
         workspace,
         children,
         dependencies: match workspace {
@@ -95,6 +124,16 @@ pub fn to_struct(tree: &Tree, target: Arc<Vec<Positioned<Target>>>) -> Toml {
     }
 }
 
+/// `rust-version` of a `[package]` or `[workspace.package]` table
+fn get_rust_version(value: &Value) -> Option<&Value> {
+    value
+        .as_tree()?
+        .nodes
+        .iter()
+        .find(|v| v.key.value == "rust-version")
+        .map(|v| &v.value)
+}
+
 fn get_workspace_members(value: &Value) -> Option<Vec<String>> {
     let value = value
         .as_array()?
@@ -3,6 +3,9 @@ use std::{cmp::Ordering, fmt::Display, num::ParseIntError};
 
 use serde::{Deserialize, Serialize};
 
+mod req;
+pub use req::VersionReq;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RustVersion {
     major: Option<u32>,
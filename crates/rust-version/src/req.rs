@@ -0,0 +1,226 @@
+use std::{cmp::Ordering, num::ParseIntError};
+
+use crate::RustVersion;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Tilde,
+    Caret,
+    Wildcard,
+}
+
+#[derive(Debug, Clone)]
+struct Comparator {
+    op: Op,
+    major: u32,
+    minor: Option<u32>,
+    patch: Option<u32>,
+    pre: Option<String>,
+}
+
+/// A cargo version requirement like `1.2`, `~1.2.3`, `>=1, <2` or `1.*`
+#[derive(Debug, Clone)]
+pub struct VersionReq {
+    comparators: Vec<Comparator>,
+}
+
+fn version(major: u32, minor: u32, patch: u32, pre: Option<&String>) -> RustVersion {
+    RustVersion {
+        major: Some(major),
+        minor: Some(minor),
+        patch: Some(patch),
+        pre: pre.cloned(),
+        build: None,
+    }
+}
+
+impl Comparator {
+    /// Inclusive lower and exclusive upper bound
+    fn bounds(&self) -> (Option<RustVersion>, Option<RustVersion>) {
+        let (major, pre) = (self.major, self.pre.as_ref());
+        let lower = version(major, self.minor.unwrap_or(0), self.patch.unwrap_or(0), pre);
+        // upper bound of a partial version like `1.2` or `1.*`
+        let partial_upper = || match (self.minor, self.patch) {
+            (None, _) => version(major + 1, 0, 0, None),
+            (Some(minor), None) => version(major, minor + 1, 0, None),
+            (Some(minor), Some(patch)) => version(major, minor, patch + 1, None),
+        };
+        match self.op {
+            Op::Exact | Op::Wildcard => (Some(lower), Some(partial_upper())),
+            Op::GreaterEq => (Some(lower), None),
+            Op::Greater => match (self.minor, self.patch) {
+                (Some(minor), Some(patch)) => (Some(version(major, minor, patch + 1, None)), None),
+                _ => (Some(partial_upper()), None),
+            },
+            Op::Less => (None, Some(lower)),
+            Op::LessEq => (None, Some(partial_upper())),
+            Op::Tilde => match self.minor {
+                Some(minor) => (Some(lower), Some(version(major, minor + 1, 0, None))),
+                None => (Some(lower), Some(version(major + 1, 0, 0, None))),
+            },
+            Op::Caret => {
+                let upper = match (major, self.minor, self.patch) {
+                    (0, Some(0), Some(patch)) => version(0, 0, patch + 1, None),
+                    (0, Some(minor), _) => version(0, minor + 1, 0, None),
+                    (major, _, _) => version(major + 1, 0, 0, None),
+                };
+                (Some(lower), Some(upper))
+            }
+        }
+    }
+
+    fn matches(&self, ver: &RustVersion) -> bool {
+        let ver = version(
+            ver.major.unwrap_or(0),
+            ver.minor.unwrap_or(0),
+            ver.patch.unwrap_or(0),
+            ver.pre.as_ref(),
+        );
+        let (lower, upper) = self.bounds();
+        let above = lower.map(|v| ver.cmp(&v) != Ordering::Less).unwrap_or(true);
+        let below = upper.map(|v| ver.cmp(&v) == Ordering::Less).unwrap_or(true);
+        // 1.2.3-pre is lower than 1.2.3, so `<1.2.3` would accept it
+        let pre_of_upper = self.op == Op::Less
+            && self.pre.is_none()
+            && ver.pre.is_some()
+            && (ver.major, ver.minor, ver.patch)
+                == (
+                    Some(self.major),
+                    self.minor.or(Some(0)),
+                    self.patch.or(Some(0)),
+                );
+        above && below && !pre_of_upper
+    }
+
+    fn allows_pre_of(&self, ver: &RustVersion) -> bool {
+        self.pre.is_some()
+            && ver.major == Some(self.major)
+            && ver.minor == self.minor
+            && ver.patch == self.patch
+    }
+}
+
+impl VersionReq {
+    /// Pre-releases only match if a comparator names the same version with a pre-release
+    pub fn matches(&self, ver: &RustVersion) -> bool {
+        if ver.is_pre_release() && !self.comparators.iter().any(|v| v.allows_pre_of(ver)) {
+            return false;
+        }
+        self.comparators.iter().all(|v| v.matches(ver))
+    }
+
+    /// A single `^`, `~` or `=` comparator without wildcards, like `1.2` or `=1.2.3`
+    pub fn is_simple(&self) -> bool {
+        matches!(
+            self.comparators.as_slice(),
+            [Comparator {
+                op: Op::Caret | Op::Tilde | Op::Exact,
+                ..
+            }]
+        )
+    }
+}
+
+impl TryFrom<&str> for VersionReq {
+    type Error = ParseIntError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut comparators = vec![];
+        for part in value.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+            let (op, rest) = [
+                (">=", Op::GreaterEq),
+                ("<=", Op::LessEq),
+                (">", Op::Greater),
+                ("<", Op::Less),
+                ("=", Op::Exact),
+                ("~", Op::Tilde),
+                ("^", Op::Caret),
+            ]
+            .into_iter()
+            .find_map(|(prefix, op)| part.strip_prefix(prefix).map(|rest| (op, rest)))
+            .unwrap_or((Op::Caret, part));
+            let rest = rest.trim();
+            let rest = rest.split_once('+').map(|v| v.0).unwrap_or(rest);
+            let (core, pre) = match rest.split_once('-') {
+                Some((core, pre)) => (core, Some(pre.to_owned())),
+                None => (rest, None),
+            };
+            let mut numbers = vec![];
+            let mut wildcard = false;
+            for item in core.splitn(3, '.') {
+                if matches!(item, "*" | "x" | "X") {
+                    wildcard = true;
+                    break;
+                }
+                numbers.push(item.parse::<u32>()?);
+            }
+            let Some(major) = numbers.first().copied() else {
+                // `*` matches everything
+                continue;
+            };
+            comparators.push(Comparator {
+                op: match (wildcard, op) {
+                    (true, Op::Caret | Op::Exact) => Op::Wildcard,
+                    _ => op,
+                },
+                major,
+                minor: numbers.get(1).copied(),
+                patch: numbers.get(2).copied(),
+                pre,
+            });
+        }
+        Ok(Self { comparators })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RustVersion, VersionReq};
+
+    fn matches(req: &str, ver: &str) -> bool {
+        VersionReq::try_from(req)
+            .unwrap()
+            .matches(&RustVersion::try_from(ver).unwrap())
+    }
+
+    #[test]
+    fn caret() {
+        assert!(matches("1.2.3", "1.9.0"));
+        assert!(!matches("1.2.3", "1.2.2"));
+        assert!(!matches("1.2.3", "2.0.0"));
+        assert!(matches("0.2.3", "0.2.9"));
+        assert!(!matches("0.2.3", "0.3.0"));
+        assert!(!matches("0.0.3", "0.0.4"));
+        assert!(matches("0", "0.9.9"));
+    }
+
+    #[test]
+    fn operators() {
+        assert!(matches("~1.2.3", "1.2.9"));
+        assert!(!matches("~1.2.3", "1.3.0"));
+        assert!(matches("=1.2.3", "1.2.3"));
+        assert!(!matches("=1.2.3", "1.2.4"));
+        assert!(matches(">=1.2, <1.5", "1.4.9"));
+        assert!(!matches(">=1.2, <1.5", "1.5.0"));
+        assert!(matches(">1.2", "1.3.0"));
+        assert!(!matches(">1.2", "1.2.9"));
+        assert!(matches("<=1.2", "1.2.9"));
+        assert!(matches("1.*", "1.9.0"));
+        assert!(matches("*", "3.0.0"));
+        let simple = |v| VersionReq::try_from(v).unwrap().is_simple();
+        assert!(simple("1.2") && simple("~1.2.3") && simple("= 1.2.3"));
+        assert!(!simple(">=1, <2") && !simple("1.*") && !simple("*"));
+    }
+
+    #[test]
+    fn pre_release() {
+        assert!(!matches("1.0.0", "1.1.0-alpha"));
+        assert!(matches("1.1.0-alpha", "1.1.0-beta"));
+        assert!(!matches("<1.1.0", "1.1.0-alpha"));
+    }
+}